@@ -0,0 +1,3 @@
+#!/bin/bash
+
+bash $( dirname -- "$0"; )/run_workload.sh bounded-counter "$@"
\ No newline at end of file
@@ -1,3 +1,3 @@
 #!/bin/bash
 
-bash $( dirname -- "$0"; )/run_workload.sh broadcast
\ No newline at end of file
+bash $( dirname -- "$0"; )/run_workload.sh broadcast "$@"
\ No newline at end of file
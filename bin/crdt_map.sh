@@ -0,0 +1,3 @@
+#!/bin/bash
+
+bash $( dirname -- "$0"; )/run_workload.sh crdt-map "$@"
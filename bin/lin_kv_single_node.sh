@@ -1,3 +1,3 @@
 #!/bin/bash
 
-bash $( dirname -- "$0"; )/run_workload.sh lin-kv-single-node
\ No newline at end of file
+bash $( dirname -- "$0"; )/run_workload.sh lin-kv-single-node "$@"
\ No newline at end of file
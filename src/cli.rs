@@ -0,0 +1,195 @@
+//! Command line parsing: `demo <workload> [--option value]...` and `demo list`.
+//!
+//! Every option can also be set with an env var, e.g. `--retry-interval-ms`
+//! with `DEMO_RETRY_INTERVAL_MS`. Command line values take precedence.
+
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use tokio::time::Duration;
+
+const ENV_PREFIX: &str = "DEMO_";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    MissingWorkload,
+    UnknownWorkload(String),
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue {
+        option: String,
+        value: String,
+        reason: String,
+    },
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::MissingWorkload => write!(f, "missing workload"),
+            CliError::UnknownWorkload(name) => write!(f, "unknown workload '{name}'"),
+            CliError::UnknownOption(name) => write!(f, "unknown option '{name}'"),
+            CliError::MissingValue(name) => write!(f, "missing value of option '--{name}'"),
+            CliError::InvalidValue {
+                option,
+                value,
+                reason,
+            } => write!(f, "invalid value '{value}' of option '--{option}': {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Option accepted by a workload.
+#[derive(Debug)]
+pub struct OptionSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub default: &'static str,
+}
+
+impl OptionSpec {
+    pub fn env_var(&self) -> String {
+        format!("{ENV_PREFIX}{}", self.name.replace('-', "_").to_uppercase())
+    }
+}
+
+pub enum Command {
+    List,
+    Run { workload: String, args: Vec<String> },
+}
+
+pub fn parse_command(args: &[String]) -> Result<Command, CliError> {
+    match args.split_first() {
+        None => Err(CliError::MissingWorkload),
+        Some((cmd, _)) if cmd == "list" || cmd == "--help" || cmd == "-h" => Ok(Command::List),
+        Some((workload, rest)) => Ok(Command::Run {
+            workload: workload.clone(),
+            args: rest.to_vec(),
+        }),
+    }
+}
+
+/// Option values of a workload, resolved from the command line, env vars and defaults.
+#[derive(Debug)]
+pub struct Options {
+    values: HashMap<&'static str, String>,
+}
+
+impl Options {
+    pub fn parse(specs: &'static [OptionSpec], args: &[String]) -> Result<Self, CliError> {
+        Self::parse_with_env(specs, args, |var| std::env::var(var).ok())
+    }
+
+    fn parse_with_env(
+        specs: &'static [OptionSpec],
+        args: &[String],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, CliError> {
+        let mut values: HashMap<_, _> = specs
+            .iter()
+            .map(|spec| {
+                let value = env(&spec.env_var()).unwrap_or_else(|| spec.default.to_owned());
+                (spec.name, value)
+            })
+            .collect();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("--") else {
+                return Err(CliError::UnknownOption(arg.clone()));
+            };
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, value.to_owned()),
+                None => (
+                    option,
+                    args.next()
+                        .ok_or_else(|| CliError::MissingValue(option.to_owned()))?
+                        .clone(),
+                ),
+            };
+            let spec = specs
+                .iter()
+                .find(|spec| spec.name == name)
+                .ok_or_else(|| CliError::UnknownOption(arg.clone()))?;
+            values.insert(spec.name, value);
+        }
+        Ok(Self { values })
+    }
+
+    pub fn get<T: FromStr>(&self, name: &str) -> Result<T, CliError>
+    where
+        T::Err: Display,
+    {
+        let value = self
+            .values
+            .get(name)
+            .ok_or_else(|| CliError::UnknownOption(name.to_owned()))?;
+        value.parse().map_err(|err: T::Err| CliError::InvalidValue {
+            option: name.to_owned(),
+            value: value.clone(),
+            reason: err.to_string(),
+        })
+    }
+
+    /// Reads an option given in milliseconds.
+    pub fn millis(&self, name: &str) -> Result<Duration, CliError> {
+        self.get(name).map(Duration::from_millis)
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::{CliError, OptionSpec, Options};
+    use tokio::time::Duration;
+
+    static SPECS: &[OptionSpec] = &[
+        OptionSpec {
+            name: "interval-ms",
+            description: "",
+            default: "1000",
+        },
+        OptionSpec {
+            name: "retries",
+            description: "",
+            default: "2",
+        },
+    ];
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn defaults_env_and_args() {
+        let env = |var: &str| (var == "DEMO_RETRIES").then(|| "5".to_owned());
+        let options = Options::parse_with_env(SPECS, &[], env).unwrap();
+        assert_eq!(options.millis("interval-ms"), Ok(Duration::from_secs(1)));
+        assert_eq!(options.get::<u32>("retries"), Ok(5));
+
+        let options =
+            Options::parse_with_env(SPECS, &args(&["--retries", "7", "--interval-ms=10"]), env)
+                .unwrap();
+        assert_eq!(options.millis("interval-ms"), Ok(Duration::from_millis(10)));
+        assert_eq!(options.get::<u32>("retries"), Ok(7));
+    }
+
+    #[test]
+    fn invalid_args() {
+        let parse = |a: &[&str]| Options::parse_with_env(SPECS, &args(a), |_| None).unwrap_err();
+        assert_eq!(
+            parse(&["--timeout", "1"]),
+            CliError::UnknownOption("--timeout".to_owned())
+        );
+        assert_eq!(
+            parse(&["--retries"]),
+            CliError::MissingValue("retries".to_owned())
+        );
+        assert_eq!(parse(&["1"]), CliError::UnknownOption("1".to_owned()));
+
+        let options = Options::parse_with_env(SPECS, &args(&["--retries=x"]), |_| None).unwrap();
+        assert!(matches!(
+            options.get::<u32>("retries"),
+            Err(CliError::InvalidValue { .. })
+        ));
+    }
+}
@@ -0,0 +1,419 @@
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::time::Duration;
+
+use super::sync_resp::{RpcError, RpcMetrics, SyncRespHandler};
+use crate::protocol::{
+    gen_next_msg_id,
+    kv::{BodyData, Message},
+    Body, ErrorCode, ErrorData, NodeId,
+};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+pub const DEFAULT_RETRIES: u32 = 3;
+const EVENTUAL_READ_RETRIES: u32 = 20;
+const EVENTUAL_READ_MIN_DELAY: Duration = Duration::from_millis(10);
+const EVENTUAL_READ_MAX_DELAY: Duration = Duration::from_millis(200);
+const EVENTUAL_READ_DEADLINE: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum KvError {
+    KeyDoesNotExist,
+    PreconditionFailed(String),
+    TemporarilyUnavailable(String),
+    /// The service replied with an error the client doesn't handle itself.
+    Service(ErrorData),
+    /// No reply, the request might or might not have been applied.
+    Rpc(RpcError),
+    InvalidValue(serde_json::Error),
+    UnexpectedResponse(String),
+}
+
+impl From<ErrorData> for KvError {
+    fn from(data: ErrorData) -> Self {
+        match data.code {
+            ErrorCode::KeyDoesNotExist => KvError::KeyDoesNotExist,
+            ErrorCode::PreconditionFailed => KvError::PreconditionFailed(data.text),
+            ErrorCode::TemporarilyUnavailable => KvError::TemporarilyUnavailable(data.text),
+            _ => KvError::Service(data),
+        }
+    }
+}
+
+impl From<RpcError> for KvError {
+    fn from(err: RpcError) -> Self {
+        KvError::Rpc(err)
+    }
+}
+
+impl From<serde_json::Error> for KvError {
+    fn from(err: serde_json::Error) -> Self {
+        KvError::InvalidValue(err)
+    }
+}
+
+impl From<KvError> for ErrorData {
+    fn from(err: KvError) -> Self {
+        let code = match err {
+            KvError::KeyDoesNotExist => ErrorCode::KeyDoesNotExist,
+            KvError::PreconditionFailed(_) => ErrorCode::PreconditionFailed,
+            KvError::TemporarilyUnavailable(_) => ErrorCode::TemporarilyUnavailable,
+            KvError::Service(data) => return data,
+            KvError::Rpc(_) => ErrorCode::Timeout,
+            KvError::InvalidValue(_) | KvError::UnexpectedResponse(_) => ErrorCode::Abort,
+        };
+        ErrorData::new(err.to_string(), code)
+    }
+}
+
+impl std::fmt::Display for KvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KvError::KeyDoesNotExist => write!(f, "key does not exist"),
+            KvError::PreconditionFailed(text) => write!(f, "precondition failed: {text}"),
+            KvError::TemporarilyUnavailable(text) => write!(f, "temporarily unavailable: {text}"),
+            KvError::Service(data) => write!(f, "service error {:?}: {}", data.code, data.text),
+            KvError::Rpc(err) => write!(f, "storage request failed: {err}"),
+            KvError::InvalidValue(err) => write!(f, "invalid stored value: {err}"),
+            KvError::UnexpectedResponse(resp) => write!(f, "unexpected response: {resp}"),
+        }
+    }
+}
+
+impl std::error::Error for KvError {}
+
+/// Typed client of the Maelstrom key-value services (`lin-kv`, `seq-kv`, `lww-kv`)
+/// and of the `lin-tso` timestamp oracle.
+///
+/// The workload has to route the service replies to [`KvClient::handle`].
+pub struct KvClient {
+    node_id: NodeId,
+    service: &'static str,
+    sync_resp: SyncRespHandler<BodyData>,
+    timeout: Duration,
+    retries: u32,
+}
+
+impl KvClient {
+    pub fn new(node_id: NodeId, service: &'static str) -> Self {
+        Self {
+            node_id,
+            service,
+            sync_resp: SyncRespHandler::new(),
+            timeout: DEFAULT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Resends of reads and writes, `cas` is never resent.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn service(&self) -> &'static str {
+        self.service
+    }
+
+    pub fn metrics(&self) -> RpcMetrics {
+        self.sync_resp.metrics()
+    }
+
+    pub async fn handle(&self, msg: Message) {
+        self.sync_resp.handle(msg).await;
+    }
+
+    pub async fn read<K: Serialize, V: DeserializeOwned>(&self, key: &K) -> Result<V, KvError> {
+        let resp = self
+            .sync_resp
+            .request(self.create_req(BodyData::Read {
+                key: serde_json::to_value(key)?,
+            }))
+            .idempotent()
+            .retries(self.retries)
+            .attempt_timeout(self.timeout)
+            .send()
+            .await?;
+        match resp.body.data {
+            BodyData::ReadOk { value } => Ok(serde_json::from_value(value)?),
+            other => Err(unexpected_resp(other)),
+        }
+    }
+
+    /// Reads a key that is known to be written, retrying while the write
+    /// hasn't reached the replica serving the read (`seq-kv`, `lww-kv`).
+    pub async fn read_eventually<K: Serialize, V: DeserializeOwned>(
+        &self,
+        key: &K,
+    ) -> Result<V, KvError> {
+        let resp = self
+            .sync_resp
+            .request(self.create_req(BodyData::Read {
+                key: serde_json::to_value(key)?,
+            }))
+            .idempotent()
+            .retries(EVENTUAL_READ_RETRIES)
+            .attempt_timeout(self.timeout)
+            .deadline(EVENTUAL_READ_DEADLINE)
+            .backoff(EVENTUAL_READ_MIN_DELAY, EVENTUAL_READ_MAX_DELAY)
+            .retry_on(|resp| {
+                matches!(
+                    resp.body.data,
+                    BodyData::Error(ErrorData {
+                        code: ErrorCode::KeyDoesNotExist,
+                        ..
+                    })
+                )
+            })
+            .send()
+            .await?;
+        match resp.body.data {
+            BodyData::ReadOk { value } => Ok(serde_json::from_value(value)?),
+            other => Err(unexpected_resp(other)),
+        }
+    }
+
+    pub async fn write<K: Serialize, V: Serialize>(
+        &self,
+        key: &K,
+        value: &V,
+    ) -> Result<(), KvError> {
+        let resp = self
+            .sync_resp
+            .request(self.create_req(BodyData::Write {
+                key: serde_json::to_value(key)?,
+                value: serde_json::to_value(value)?,
+            }))
+            .idempotent()
+            .retries(self.retries)
+            .attempt_timeout(self.timeout)
+            .send()
+            .await?;
+        match resp.body.data {
+            BodyData::WriteOk => Ok(()),
+            other => Err(unexpected_resp(other)),
+        }
+    }
+
+    pub async fn cas<K: Serialize, V: Serialize>(
+        &self,
+        key: &K,
+        from: &V,
+        to: &V,
+        create_if_not_exists: bool,
+    ) -> Result<(), KvError> {
+        let resp = self
+            .sync_resp
+            .request(self.create_req(BodyData::Cas {
+                key: serde_json::to_value(key)?,
+                from: serde_json::to_value(from)?,
+                to: serde_json::to_value(to)?,
+                create_if_not_exists,
+            }))
+            .attempt_timeout(self.timeout)
+            .send()
+            .await?;
+        match resp.body.data {
+            BodyData::CasOk => Ok(()),
+            other => Err(unexpected_resp(other)),
+        }
+    }
+
+    /// Fetches a timestamp from `lin-tso`, strictly greater than any previously issued one.
+    #[allow(dead_code)]
+    pub async fn ts(&self) -> Result<u64, KvError> {
+        let resp = self
+            .sync_resp
+            .request(self.create_req(BodyData::Ts))
+            .idempotent()
+            .retries(self.retries)
+            .attempt_timeout(self.timeout)
+            .send()
+            .await?;
+        match resp.body.data {
+            BodyData::TsOk { ts } => Ok(ts),
+            other => Err(unexpected_resp(other)),
+        }
+    }
+
+    fn create_req(&self, data: BodyData) -> Message {
+        Message {
+            src: self.node_id.clone(),
+            dest: self.service.to_owned(),
+            body: Body {
+                msg_id: Some(gen_next_msg_id()),
+                in_reply_to: None,
+                data,
+            },
+        }
+    }
+}
+
+/// Hands a storage service reply received by a workload over to the client
+/// of that service.
+pub async fn handle_storage_resp<T: Serialize + std::fmt::Debug>(
+    clients: &[&KvClient],
+    msg: crate::protocol::Message<T>,
+) {
+    match clients.iter().find(|kv| kv.service() == msg.src) {
+        Some(kv) => match msg.convert() {
+            Ok(resp) => kv.handle(resp).await,
+            Err(err) => log::warn!("Failed to decode storage response {msg:?}: {err}"),
+        },
+        None => log::warn!("Ignoring unexpected message {:?}", msg),
+    }
+}
+
+fn unexpected_resp(body: BodyData) -> KvError {
+    match body {
+        BodyData::Error(data) => data.into(),
+        other => KvError::UnexpectedResponse(format!("{other:?}")),
+    }
+}
+
+#[cfg(test)]
+mod kv_client_tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use super::{KvClient, KvError};
+    use crate::io::transport::{ChannelNetwork, ChannelTransport, Transport};
+    use crate::protocol::{kv::*, ErrorCode, ErrorData};
+
+    fn setup() -> (KvClient, ChannelTransport) {
+        setup_service(LIN_KV_SERVICE)
+    }
+
+    fn setup_service(service: &'static str) -> (KvClient, ChannelTransport) {
+        let network = ChannelNetwork::new();
+        crate::io::set_transport(Arc::new(network.connect("n1")));
+        (
+            KvClient::new("n1".to_owned(), service),
+            network.connect(service),
+        )
+    }
+
+    async fn reply(
+        client: &KvClient,
+        service: &ChannelTransport,
+        f: impl Fn(&BodyData) -> BodyData,
+    ) {
+        let req: Message = serde_json::from_str(&service.recv().await.unwrap()).unwrap();
+        client.handle(req.create_response(f(&req.body.data))).await;
+    }
+
+    #[tokio::test]
+    async fn typed_read() {
+        let (client, service) = setup();
+        let respond = reply(&client, &service, |req| {
+            assert!(matches!(req, BodyData::Read { key } if *key == json!([1, "a"])));
+            BodyData::ReadOk {
+                value: json!({"x": [1, 2]}),
+            }
+        });
+        let (value, _) = tokio::join!(
+            client.read::<_, std::collections::HashMap<String, Vec<u32>>>(&(1, "a")),
+            respond
+        );
+        assert_eq!(value.unwrap()["x"], vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn cas_maps_error_codes() {
+        let (client, service) = setup();
+        let respond = async {
+            for code in [
+                ErrorCode::PreconditionFailed,
+                ErrorCode::KeyDoesNotExist,
+                ErrorCode::Crash,
+            ] {
+                reply(&client, &service, |_| {
+                    BodyData::Error(ErrorData::new("failed".to_owned(), code))
+                })
+                .await;
+            }
+        };
+        let requests = async {
+            let mut errors = Vec::new();
+            for _ in 0..3 {
+                errors.push(client.cas(&"k", &1, &2, false).await.unwrap_err());
+            }
+            errors
+        };
+        let (errors, _) = tokio::join!(requests, respond);
+        assert!(matches!(errors[0], KvError::PreconditionFailed(_)));
+        assert!(matches!(errors[1], KvError::KeyDoesNotExist));
+        assert!(matches!(
+            errors[2],
+            KvError::Service(ErrorData {
+                code: ErrorCode::Crash,
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn read_eventually_waits_for_write() {
+        let (client, service) = setup();
+        let respond = async {
+            reply(&client, &service, |_| {
+                BodyData::Error(ErrorData::new(
+                    "missing".to_owned(),
+                    ErrorCode::KeyDoesNotExist,
+                ))
+            })
+            .await;
+            reply(&client, &service, |_| BodyData::ReadOk { value: json!(5) }).await;
+        };
+        let (value, _) = tokio::join!(client.read_eventually::<_, u32>(&"k"), respond);
+        assert_eq!(value.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn seq_kv_read_eventually_sees_write() {
+        let (client, service) = setup_service(SEQ_KV_SERVICE);
+        let respond = async {
+            reply(&client, &service, |req| {
+                assert!(matches!(req, BodyData::Write { key, value } if *key == json!("k") && *value == json!(5)));
+                BodyData::WriteOk
+            })
+            .await;
+            reply(&client, &service, |_| {
+                BodyData::Error(ErrorData::new(
+                    "missing".to_owned(),
+                    ErrorCode::KeyDoesNotExist,
+                ))
+            })
+            .await;
+            reply(&client, &service, |_| BodyData::ReadOk { value: json!(5) }).await;
+        };
+        let requests = async {
+            client.write(&"k", &5).await.unwrap();
+            client.read_eventually::<_, u32>(&"k").await
+        };
+        let (value, _) = tokio::join!(requests, respond);
+        assert_eq!(value.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn lin_tso_issues_timestamps() {
+        let (client, service) = setup_service(LIN_TSO_SERVICE);
+        let respond = async {
+            for ts in [7, 8] {
+                reply(&client, &service, |req| {
+                    assert!(matches!(req, BodyData::Ts));
+                    BodyData::TsOk { ts }
+                })
+                .await;
+            }
+        };
+        let requests = async { (client.ts().await.unwrap(), client.ts().await.unwrap()) };
+        let (timestamps, _) = tokio::join!(requests, respond);
+        assert_eq!(timestamps, (7, 8));
+    }
+}
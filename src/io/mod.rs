@@ -1,37 +1,65 @@
+use std::{cell::RefCell, sync::Arc};
+
 use crate::protocol::Message;
 
+pub mod kv_client;
 pub mod sync_resp;
+pub mod transport;
+
+use transport::Transport;
+
+thread_local! {
+    static TRANSPORT: RefCell<Option<Arc<dyn Transport>>> = RefCell::new(None);
+}
+
+/// Sets the transport used by the node running on the current thread.
+/// Workloads run on a current thread runtime, so every task of the node shares it.
+pub fn set_transport(transport: Arc<dyn Transport>) {
+    TRANSPORT.with(|t| *t.borrow_mut() = Some(transport));
+}
+
+fn transport() -> Arc<dyn Transport> {
+    TRANSPORT.with(|t| t.borrow_mut().get_or_insert_with(transport::stdio).clone())
+}
 
 pub mod blocking {
     use crate::protocol::Message;
 
     pub fn receive_msg<T: serde::de::DeserializeOwned>() -> Message<T> {
-        let mut buf = String::new();
-        std::io::stdin().read_line(&mut buf).unwrap();
+        let buf = super::transport()
+            .recv_blocking()
+            .expect("Transport closed");
+        log::debug!("Received {buf}");
         serde_json::from_str(&buf).unwrap()
     }
 }
 
 pub mod non_blocking {
-    use crate::protocol::Message;
+    use crate::{logging::MsgSpan, protocol::Message};
 
     pub async fn receive_msg<T: serde::de::DeserializeOwned>() -> Message<T> {
-        use once_cell::sync::Lazy;
-        use tokio::{
-            io::{AsyncBufReadExt, BufReader, Stdin},
-            sync::Mutex,
-        };
-        static BUF_READER: Lazy<Mutex<BufReader<Stdin>>> = Lazy::new(|| {
-            Mutex::new(BufReader::new(tokio::io::stdin()))
-        });
-        let mut buf = String::new();
-        let mut reader = BUF_READER.lock().await;
-        reader.read_line(&mut buf).await.unwrap();
-        serde_json::from_str(&buf).unwrap()
+        serde_json::from_str(&receive_line().await).unwrap()
+    }
+
+    /// Also returns the span of the message, read from the line so that
+    /// large bodies aren't encoded again to find their type.
+    pub async fn receive_msg_in_span<T: serde::de::DeserializeOwned>() -> (Message<T>, MsgSpan) {
+        let buf = receive_line().await;
+        (
+            serde_json::from_str(&buf).unwrap(),
+            MsgSpan::of_line(&buf).unwrap(),
+        )
+    }
+
+    async fn receive_line() -> String {
+        let buf = super::transport().recv().await.expect("Transport closed");
+        log::debug!("Received {buf}");
+        buf
     }
 }
 
 pub fn send_msg<T: serde::Serialize>(msg: &Message<T>) {
     let s = serde_json::to_string(msg).unwrap();
-    println!("{}", s);
-}
\ No newline at end of file
+    log::debug!("Sending {s}");
+    transport().send(s);
+}
@@ -1,39 +1,513 @@
-use std::{collections::HashMap, fmt::Debug, sync::Mutex};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Debug,
+    sync::Mutex,
+};
 
-use crate::protocol::{Message, MessageId};
+use crate::protocol::{gen_next_msg_id, Message, MessageId};
+use rand::Rng;
 use serde::Serialize;
-use tokio::{sync::oneshot, time::Duration};
+use tokio::{
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::{sleep, timeout_at, Duration, Instant},
+};
 
 use super::send_msg;
 
+pub const DEFAULT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(1);
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(10);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(1);
+/// How often [`log_metrics`] checks the metrics for changes.
+const METRICS_LOG_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of finished attempts remembered to classify unexpected replies.
+const FINISHED_RETENTION: usize = 10_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    /// None of the attempts got a response in time.
+    Timeout { attempts: u32 },
+    /// The overall deadline of the request passed.
+    DeadlineExceeded { attempts: u32 },
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RpcError::Timeout { attempts } => write!(f, "no response after {attempts} attempts"),
+            RpcError::DeadlineExceeded { attempts } => {
+                write!(f, "deadline exceeded after {attempts} attempts")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Counters of failed requests and of replies that completed none.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RpcMetrics {
+    /// Requests that failed without any response.
+    pub timeouts: u64,
+    /// Replies arriving after their request had already given up on the attempt.
+    pub late_replies: u64,
+    /// Repeated replies to an already answered attempt.
+    pub duplicate_replies: u64,
+    /// Replies to message ids this node doesn't know about.
+    pub unsolicited_replies: u64,
+}
+
+enum FinishedAttempt {
+    Answered,
+    Abandoned(Instant),
+}
+
+#[derive(Default)]
+struct FinishedAttempts {
+    order: VecDeque<MessageId>,
+    attempts: HashMap<MessageId, FinishedAttempt>,
+}
+
+impl FinishedAttempts {
+    fn insert(&mut self, msg_id: MessageId, attempt: FinishedAttempt) {
+        if self.attempts.insert(msg_id, attempt).is_none() {
+            self.order.push_back(msg_id);
+            if self.order.len() > FINISHED_RETENTION {
+                let oldest = self.order.pop_front().unwrap();
+                self.attempts.remove(&oldest);
+            }
+        }
+    }
+}
+
+struct HandlerState<T> {
+    pending: HashMap<MessageId, UnboundedSender<Message<T>>>,
+    finished: FinishedAttempts,
+    metrics: RpcMetrics,
+}
+
 pub struct SyncRespHandler<T> {
-    pending: Mutex<HashMap<MessageId, oneshot::Sender<Message<T>>>>,
+    state: Mutex<HandlerState<T>>,
 }
 
 impl<T: Debug> SyncRespHandler<T> {
     pub fn new() -> Self {
         Self {
-            pending: Mutex::new(HashMap::new()),
+            state: Mutex::new(HandlerState {
+                pending: HashMap::new(),
+                finished: FinishedAttempts::default(),
+                metrics: RpcMetrics::default(),
+            }),
         }
     }
 
     pub async fn handle(&self, msg: Message<T>) {
-        if let Some(ref msg_id) = msg.body.in_reply_to {
-            if let Some(sender) = self.pending.lock().unwrap().remove(msg_id) {
-                sender
-                    .send(msg)
-                    .unwrap_or_else(|resp| eprintln!("Failed to handle response: {resp:?}"));
+        let Some(msg_id) = msg.body.in_reply_to else {
+            log::warn!("Ignoring response without in_reply_to: {msg:?}");
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        if let Some(sender) = state.pending.remove(&msg_id) {
+            state.finished.insert(msg_id, FinishedAttempt::Answered);
+            drop(state);
+            sender
+                .send(msg)
+                .unwrap_or_else(|resp| log::debug!("Failed to handle response: {:?}", resp.0));
+            return;
+        }
+        let HandlerState {
+            finished, metrics, ..
+        } = &mut *state;
+        match finished.attempts.get(&msg_id) {
+            Some(FinishedAttempt::Answered) => {
+                metrics.duplicate_replies += 1;
+                log::warn!(
+                    "Duplicate reply to {msg_id} from {} (duplicate replies: {})",
+                    msg.src,
+                    metrics.duplicate_replies
+                );
+            }
+            Some(FinishedAttempt::Abandoned(since)) => {
+                metrics.late_replies += 1;
+                log::info!(
+                    "Late reply to {msg_id} from {}, {:?} after giving up (late replies: {}, timeouts: {})",
+                    msg.src,
+                    since.elapsed(),
+                    metrics.late_replies,
+                    metrics.timeouts
+                );
+            }
+            None => {
+                metrics.unsolicited_replies += 1;
+                log::warn!(
+                    "Unsolicited reply to {msg_id} from {} (unsolicited replies: {})",
+                    msg.src,
+                    metrics.unsolicited_replies
+                );
+            }
+        }
+    }
+
+    /// Starts building a request, see [`Request`] for the available options.
+    pub fn request<R: Serialize>(&self, msg: Message<R>) -> Request<'_, T, R> {
+        Request {
+            handler: self,
+            msg,
+            retries: 0,
+            idempotent: false,
+            attempt_timeout: DEFAULT_ATTEMPT_TIMEOUT,
+            deadline: None,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            retry_on: None,
+        }
+    }
+
+    fn register(&self, msg_id: MessageId, sender: UnboundedSender<Message<T>>) {
+        self.state.lock().unwrap().pending.insert(msg_id, sender);
+    }
+
+    fn record_failure(&self, err: &RpcError) {
+        let mut state = self.state.lock().unwrap();
+        state.metrics.timeouts += 1;
+        log::warn!(
+            "Request failed: {err} (timeouts: {}, late replies: {})",
+            state.metrics.timeouts,
+            state.metrics.late_replies
+        );
+    }
+
+    #[cfg(test)]
+    fn pending_len(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+
+    pub fn metrics(&self) -> RpcMetrics {
+        self.state.lock().unwrap().metrics
+    }
+}
+
+/// Logs the metrics of a client whenever they changed since the last check.
+pub fn log_metrics<F>(client: &'static str, metrics: F)
+where
+    F: Fn() -> RpcMetrics + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut logged = RpcMetrics::default();
+        loop {
+            sleep(METRICS_LOG_INTERVAL).await;
+            let current = metrics();
+            if current != logged {
+                log::info!("RPC metrics of {client}: {current:?}");
+                logged = current;
+            }
+        }
+    });
+}
+
+type RetryPredicate<'a, T> = Box<dyn Fn(&Message<T>) -> bool + Send + Sync + 'a>;
+
+/// A request sent through [`SyncRespHandler`].
+///
+/// Every attempt is sent with a fresh `msg_id`, and a reply to any of them
+/// completes the request. Requests are sent once unless marked idempotent, as a
+/// timed out attempt might still have been applied by the receiver. Dropping
+/// the future cancels the request and forgets all its attempts.
+pub struct Request<'a, T, R> {
+    handler: &'a SyncRespHandler<T>,
+    msg: Message<R>,
+    retries: u32,
+    idempotent: bool,
+    attempt_timeout: Duration,
+    deadline: Option<Duration>,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    retry_on: Option<RetryPredicate<'a, T>>,
+}
+
+impl<'a, T: Debug, R: Serialize> Request<'a, T, R> {
+    /// Number of resends after the first attempt, ignored unless the request
+    /// is marked [`Request::idempotent`].
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Marks the request as safe for the receiver to apply more than once,
+    /// which is what allows resending it.
+    pub fn idempotent(mut self) -> Self {
+        self.idempotent = true;
+        self
+    }
+
+    pub fn attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = timeout;
+        self
+    }
+
+    /// Overall time budget including all attempts and backoff delays.
+    pub fn deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Exponential backoff between attempts, every delay gets a random jitter of up to a half.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Treats matching responses as failed attempts, the last one is returned
+    /// once the retries are exhausted.
+    pub fn retry_on<F: Fn(&Message<T>) -> bool + Send + Sync + 'a>(mut self, f: F) -> Self {
+        self.retry_on = Some(Box::new(f));
+        self
+    }
+
+    pub async fn send(self) -> Result<Message<T>, RpcError> {
+        let handler = self.handler;
+        let res = self.send_attempts().await;
+        if let Err(ref err) = res {
+            handler.record_failure(err);
+        }
+        res
+    }
+
+    async fn send_attempts(mut self) -> Result<Message<T>, RpcError> {
+        let max_attempts = if self.idempotent { self.retries + 1 } else { 1 };
+        let deadline = self.deadline.map(|d| Instant::now() + d);
+        let (sender, mut recv) = unbounded_channel();
+        let mut attempts = PendingAttempts {
+            handler: self.handler,
+            msg_ids: Vec::new(),
+        };
+        loop {
+            let msg_id = gen_next_msg_id();
+            self.msg.body.msg_id = Some(msg_id);
+            self.handler.register(msg_id, sender.clone());
+            attempts.msg_ids.push(msg_id);
+            send_msg(&self.msg);
+            let attempt_deadline = min_deadline(Instant::now() + self.attempt_timeout, deadline);
+            let can_retry = (attempts.msg_ids.len() as u32) < max_attempts;
+            match wait_resp(&mut recv, attempt_deadline).await {
+                Some(resp) if can_retry && self.retry_on.as_ref().is_some_and(|f| f(&resp)) => {}
+                Some(resp) => return Ok(resp),
+                None => {}
+            }
+            let attempts_cnt = attempts.msg_ids.len() as u32;
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(RpcError::DeadlineExceeded {
+                    attempts: attempts_cnt,
+                });
+            }
+            if !can_retry {
+                return Err(RpcError::Timeout {
+                    attempts: attempts_cnt,
+                });
+            }
+            let backoff_deadline =
+                min_deadline(Instant::now() + self.backoff_delay(attempts_cnt), deadline);
+            // Late replies to the previous attempts are still accepted while backing off.
+            if let Some(resp) = wait_resp(&mut recv, backoff_deadline).await {
+                if !self.retry_on.as_ref().is_some_and(|f| f(&resp)) {
+                    return Ok(resp);
+                }
             }
         }
     }
 
-    pub async fn send<R: Serialize>(&self, msg: Message<R>, timeout: Duration) -> Option<Message<T>> {
-        let (send, recv) = oneshot::channel();
-        self.pending.lock().unwrap().insert(msg.body.msg_id.unwrap(), send);
-        send_msg(&msg);
-        match tokio::time::timeout(timeout, recv).await {
-            Ok(Ok(resp)) => Some(resp),
-            _ => None,
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_backoff);
+        delay / 2 + delay.mul_f64(rand::thread_rng().gen_range(0.0..0.5))
+    }
+}
+
+async fn wait_resp<T>(
+    recv: &mut UnboundedReceiver<Message<T>>,
+    deadline: Instant,
+) -> Option<Message<T>> {
+    timeout_at(deadline, recv.recv()).await.ok().flatten()
+}
+
+fn min_deadline(deadline: Instant, overall: Option<Instant>) -> Instant {
+    overall.map_or(deadline, |overall| deadline.min(overall))
+}
+
+/// Forgets the request attempts once it completes, times out or gets dropped.
+struct PendingAttempts<'a, T> {
+    handler: &'a SyncRespHandler<T>,
+    msg_ids: Vec<MessageId>,
+}
+
+impl<T> Drop for PendingAttempts<'_, T> {
+    fn drop(&mut self) {
+        let now = Instant::now();
+        let mut state = self.handler.state.lock().unwrap();
+        for msg_id in &self.msg_ids {
+            if state.pending.remove(msg_id).is_some() {
+                state
+                    .finished
+                    .insert(*msg_id, FinishedAttempt::Abandoned(now));
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod sync_resp_tests {
+    use std::sync::Arc;
+
+    use tokio::time::Duration;
+
+    use super::{RpcError, RpcMetrics, SyncRespHandler};
+    use crate::io::transport::{ChannelNetwork, ChannelTransport, Transport};
+    use crate::protocol::{echo::*, Body};
+
+    const ATTEMPT_TIMEOUT: Duration = Duration::from_millis(20);
+
+    fn setup() -> (SyncRespHandler<BodyData>, ChannelTransport) {
+        let network = ChannelNetwork::new();
+        crate::io::set_transport(Arc::new(network.connect("n1")));
+        (SyncRespHandler::new(), network.connect("n2"))
+    }
+
+    fn echo_req() -> Message {
+        Message {
+            src: "n1".to_owned(),
+            dest: "n2".to_owned(),
+            body: Body {
+                msg_id: None,
+                in_reply_to: None,
+                data: BodyData::Echo(EchoData {
+                    echo: serde_json::json!("ping"),
+                }),
+            },
+        }
+    }
+
+    async fn recv_req(peer: &ChannelTransport) -> Message {
+        serde_json::from_str(&peer.recv().await.unwrap()).unwrap()
+    }
+
+    fn echo_resp(req: &Message) -> Message {
+        req.create_response(BodyData::EchoOk(EchoData {
+            echo: serde_json::json!("pong"),
+        }))
+    }
+
+    #[tokio::test]
+    async fn retries_lost_attempt() {
+        let (handler, peer) = setup();
+        let respond = async {
+            let _lost = recv_req(&peer).await;
+            let req = recv_req(&peer).await;
+            handler.handle(echo_resp(&req)).await;
+        };
+        let request = handler
+            .request(echo_req())
+            .idempotent()
+            .retries(2)
+            .attempt_timeout(ATTEMPT_TIMEOUT)
+            .send();
+        let (resp, _) = tokio::join!(request, respond);
+        assert!(matches!(resp.unwrap().body.data, BodyData::EchoOk(_)));
+        assert_eq!(handler.pending_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn accepts_late_reply_to_previous_attempt() {
+        let (handler, peer) = setup();
+        let respond = async {
+            let first = recv_req(&peer).await;
+            let _second = recv_req(&peer).await;
+            handler.handle(echo_resp(&first)).await;
+        };
+        let request = handler
+            .request(echo_req())
+            .idempotent()
+            .retries(5)
+            .attempt_timeout(ATTEMPT_TIMEOUT)
+            .send();
+        let (resp, _) = tokio::join!(request, respond);
+        assert!(resp.is_ok());
+        assert_eq!(handler.pending_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn non_idempotent_request_is_sent_once() {
+        let (handler, _peer) = setup();
+        let resp = handler
+            .request(echo_req())
+            .retries(3)
+            .attempt_timeout(ATTEMPT_TIMEOUT)
+            .send()
+            .await;
+        assert_eq!(resp.unwrap_err(), RpcError::Timeout { attempts: 1 });
+        assert_eq!(handler.pending_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn overall_deadline() {
+        let (handler, _peer) = setup();
+        let resp = handler
+            .request(echo_req())
+            .idempotent()
+            .retries(100)
+            .attempt_timeout(ATTEMPT_TIMEOUT)
+            .deadline(3 * ATTEMPT_TIMEOUT)
+            .send()
+            .await;
+        assert!(matches!(resp, Err(RpcError::DeadlineExceeded { .. })));
+        assert_eq!(handler.pending_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn dropped_request_is_forgotten() {
+        let (handler, _peer) = setup();
+        let request = handler
+            .request(echo_req())
+            .attempt_timeout(Duration::from_secs(10))
+            .send();
+        assert!(tokio::time::timeout(ATTEMPT_TIMEOUT, request)
+            .await
+            .is_err());
+        assert_eq!(handler.pending_len(), 0);
+    }
+
+    #[tokio::test]
+    async fn classifies_unexpected_replies() {
+        let (handler, peer) = setup();
+        let respond = async {
+            let req = recv_req(&peer).await;
+            handler.handle(echo_resp(&req)).await;
+            handler.handle(echo_resp(&req)).await;
+        };
+        let (resp, _) = tokio::join!(handler.request(echo_req()).send(), respond);
+        assert!(resp.is_ok());
+
+        let request = handler
+            .request(echo_req())
+            .attempt_timeout(ATTEMPT_TIMEOUT)
+            .send();
+        let (resp, req) = tokio::join!(request, recv_req(&peer));
+        assert!(resp.is_err());
+        handler.handle(echo_resp(&req)).await;
+
+        let mut unknown = echo_resp(&req);
+        unknown.body.in_reply_to = Some(1000);
+        handler.handle(unknown).await;
+
+        assert_eq!(
+            handler.metrics(),
+            RpcMetrics {
+                timeouts: 1,
+                late_replies: 1,
+                duplicate_replies: 1,
+                unsolicited_replies: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use futures::future::BoxFuture;
+use serde::Deserialize;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::protocol::NodeId;
+
+/// Moves serialized messages (one JSON document per line) in and out of a node.
+pub trait Transport: Send + Sync {
+    fn send(&self, line: String);
+    /// Waits for the next message, `None` once the input is closed.
+    fn recv(&self) -> BoxFuture<'_, Option<String>>;
+    fn recv_blocking(&self) -> Option<String>;
+}
+
+pub const TRANSPORT_ENV: &str = "DEMO_TRANSPORT";
+pub const TCP_LISTEN_ENV: &str = "DEMO_TCP_LISTEN";
+pub const TCP_PEERS_ENV: &str = "DEMO_TCP_PEERS";
+
+/// Builds the transport selected by `DEMO_TRANSPORT`: `stdio` (default) or `tcp`.
+/// The tcp transport listens on `DEMO_TCP_LISTEN` and reaches other nodes
+/// through `DEMO_TCP_PEERS`, e.g. `n1=127.0.0.1:7001,n2=127.0.0.1:7002`.
+pub fn from_env() -> Arc<dyn Transport> {
+    match std::env::var(TRANSPORT_ENV).as_deref() {
+        Err(_) | Ok("stdio") => stdio(),
+        Ok("tcp") => {
+            let addr = std::env::var(TCP_LISTEN_ENV)
+                .unwrap_or_else(|_| panic!("{TCP_LISTEN_ENV} is required for tcp transport"));
+            let peers = std::env::var(TCP_PEERS_ENV).unwrap_or_default();
+            let transport = TcpTransport::bind(
+                addr.parse()
+                    .unwrap_or_else(|_| panic!("Invalid listen address '{addr}'")),
+                parse_peers(&peers),
+            )
+            .unwrap_or_else(|err| panic!("Failed to bind {addr}: {err}"));
+            log::info!("Listening on {}", transport.local_addr());
+            Arc::new(transport)
+        }
+        Ok(other) => panic!("Unknown transport '{other}'"),
+    }
+}
+
+/// Process-wide stdio transport, stdin can only be consumed once.
+pub fn stdio() -> Arc<dyn Transport> {
+    use once_cell::sync::Lazy;
+    static STDIO: Lazy<Arc<StdioTransport>> = Lazy::new(|| Arc::new(StdioTransport::new()));
+    STDIO.clone()
+}
+
+fn parse_peers(s: &str) -> HashMap<NodeId, SocketAddr> {
+    s.split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| match entry.trim().split_once('=') {
+            Some((node_id, addr)) => (
+                node_id.to_owned(),
+                addr.parse()
+                    .unwrap_or_else(|_| panic!("Invalid peer address '{addr}'")),
+            ),
+            None => panic!("Invalid peer '{entry}', expected <node_id>=<addr>"),
+        })
+        .collect()
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    src: NodeId,
+    dest: NodeId,
+}
+
+fn parse_envelope(line: &str) -> Option<Envelope> {
+    serde_json::from_str(line)
+        .map_err(|err| log::warn!("Failed to parse message envelope {line}: {err}"))
+        .ok()
+}
+
+struct Inbox {
+    recv: tokio::sync::Mutex<UnboundedReceiver<String>>,
+}
+
+impl Inbox {
+    fn new() -> (UnboundedSender<String>, Self) {
+        let (send, recv) = unbounded_channel();
+        (
+            send,
+            Self {
+                recv: tokio::sync::Mutex::new(recv),
+            },
+        )
+    }
+
+    fn recv(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async move { self.recv.lock().await.recv().await })
+    }
+
+    fn recv_blocking(&self) -> Option<String> {
+        self.recv.blocking_lock().blocking_recv()
+    }
+}
+
+fn forward_lines<R: BufRead>(
+    reader: R,
+    send: &UnboundedSender<String>,
+    mut on_line: impl FnMut(&str),
+) {
+    for line in reader.lines() {
+        match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => {
+                on_line(&line);
+                if send.send(line).is_err() {
+                    break;
+                }
+            }
+            Err(err) => {
+                log::error!("Failed to read input: {err}");
+                break;
+            }
+        }
+    }
+}
+
+/// Maelstrom transport: messages arrive on stdin and leave through stdout.
+pub struct StdioTransport {
+    inbox: Inbox,
+}
+
+impl StdioTransport {
+    fn new() -> Self {
+        let (send, inbox) = Inbox::new();
+        thread::spawn(move || forward_lines(std::io::stdin().lock(), &send, |_| {}));
+        Self { inbox }
+    }
+}
+
+impl Transport for StdioTransport {
+    fn send(&self, line: String) {
+        let mut stdout = std::io::stdout().lock();
+        writeln!(stdout, "{line}").unwrap();
+        stdout.flush().unwrap();
+    }
+
+    fn recv(&self) -> BoxFuture<'_, Option<String>> {
+        self.inbox.recv()
+    }
+
+    fn recv_blocking(&self) -> Option<String> {
+        self.inbox.recv_blocking()
+    }
+}
+
+/// In-process network routing messages between [`ChannelTransport`]s by `dest`.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct ChannelNetwork {
+    routes: Arc<Mutex<HashMap<NodeId, UnboundedSender<String>>>>,
+}
+
+#[cfg(test)]
+impl ChannelNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connect(&self, node_id: &str) -> ChannelTransport {
+        let (send, inbox) = Inbox::new();
+        self.routes.lock().unwrap().insert(node_id.to_owned(), send);
+        ChannelTransport {
+            network: self.clone(),
+            inbox,
+        }
+    }
+
+    fn route(&self, line: String) {
+        let Some(envelope) = parse_envelope(&line) else {
+            return;
+        };
+        match self.routes.lock().unwrap().get(&envelope.dest) {
+            Some(send) => {
+                let _ = send.send(line);
+            }
+            None => log::warn!("Dropping message to unknown node {}", envelope.dest),
+        }
+    }
+}
+
+#[cfg(test)]
+pub struct ChannelTransport {
+    network: ChannelNetwork,
+    inbox: Inbox,
+}
+
+#[cfg(test)]
+impl Transport for ChannelTransport {
+    fn send(&self, line: String) {
+        self.network.route(line);
+    }
+
+    fn recv(&self) -> BoxFuture<'_, Option<String>> {
+        self.inbox.recv()
+    }
+
+    fn recv_blocking(&self) -> Option<String> {
+        self.inbox.recv_blocking()
+    }
+}
+
+/// Local cluster transport: every node listens on its own address and connects
+/// lazily to its peers. Replies to unknown nodes (e.g. clients) go back over the
+/// connection their first message arrived on. Every connection is written by its
+/// own thread, so a slow peer never holds up sending to the others.
+pub struct TcpTransport {
+    inbox: Inbox,
+    inbox_send: UnboundedSender<String>,
+    local_addr: SocketAddr,
+    peers: HashMap<NodeId, SocketAddr>,
+    routes: Routes,
+}
+
+type Routes = Arc<Mutex<HashMap<NodeId, UnboundedSender<String>>>>;
+
+impl TcpTransport {
+    pub fn bind(addr: SocketAddr, peers: HashMap<NodeId, SocketAddr>) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let (inbox_send, inbox) = Inbox::new();
+        let routes = Routes::default();
+        {
+            let send = inbox_send.clone();
+            let routes = routes.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    match stream {
+                        Ok(stream) => spawn_reader(stream, send.clone(), routes.clone()),
+                        Err(err) => log::error!("Failed to accept connection: {err}"),
+                    }
+                }
+            });
+        }
+        Ok(Self {
+            inbox,
+            inbox_send,
+            local_addr,
+            peers,
+            routes,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Writer of the connection to `dest`, a new connection replaces a closed one.
+    fn route(&self, dest: &NodeId) -> Option<UnboundedSender<String>> {
+        let mut routes = self.routes.lock().unwrap();
+        if let Some(route) = routes.get(dest).filter(|route| !route.is_closed()) {
+            return Some(route.clone());
+        }
+        let addr = *self.peers.get(dest)?;
+        let (inbox_send, reader_routes) = (self.inbox_send.clone(), self.routes.clone());
+        let route = spawn_writer(dest.clone(), move || {
+            let stream = TcpStream::connect(addr)?;
+            spawn_reader(stream.try_clone()?, inbox_send, reader_routes);
+            Ok(stream)
+        });
+        routes.insert(dest.clone(), route.clone());
+        Some(route)
+    }
+}
+
+/// Writes the lines sent to the returned channel to the connection opened by
+/// `connect`, until the connection fails.
+fn spawn_writer(
+    dest: NodeId,
+    connect: impl FnOnce() -> std::io::Result<TcpStream> + Send + 'static,
+) -> UnboundedSender<String> {
+    let (send, mut recv) = unbounded_channel::<String>();
+    thread::spawn(move || {
+        let mut stream = match connect() {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Dropping messages to {dest}: {err}");
+                return;
+            }
+        };
+        while let Some(line) = recv.blocking_recv() {
+            if let Err(err) = writeln!(stream, "{line}") {
+                log::warn!("Failed to send message to {dest}: {err}");
+                return;
+            }
+        }
+    });
+    send
+}
+
+fn spawn_reader(stream: TcpStream, send: UnboundedSender<String>, routes: Routes) {
+    thread::spawn(move || {
+        let mut writer = stream.try_clone().ok();
+        forward_lines(BufReader::new(stream), &send, |line| {
+            let Some(writer) = writer.take() else {
+                return;
+            };
+            let Some(envelope) = parse_envelope(line) else {
+                return;
+            };
+            let mut routes = routes.lock().unwrap();
+            if routes
+                .get(&envelope.src)
+                .is_none_or(|route| route.is_closed())
+            {
+                let route = spawn_writer(envelope.src.clone(), move || Ok(writer));
+                routes.insert(envelope.src, route);
+            }
+        });
+    });
+}
+
+impl Transport for TcpTransport {
+    fn send(&self, line: String) {
+        let Some(envelope) = parse_envelope(&line) else {
+            return;
+        };
+        match self.route(&envelope.dest) {
+            Some(route) => {
+                if route.send(line).is_err() {
+                    log::warn!("Dropping message to {}: connection closed", envelope.dest);
+                }
+            }
+            None => log::warn!("Dropping message to {}: no route", envelope.dest),
+        }
+    }
+
+    fn recv(&self) -> BoxFuture<'_, Option<String>> {
+        self.inbox.recv()
+    }
+
+    fn recv_blocking(&self) -> Option<String> {
+        self.inbox.recv_blocking()
+    }
+}
+
+#[cfg(test)]
+mod transport_tests {
+    use std::{collections::HashMap, net::TcpListener, sync::Arc, thread};
+
+    use serde_json::{json, Value};
+
+    use super::{ChannelNetwork, TcpTransport, Transport};
+
+    fn send(transport: &dyn Transport, msg: Value) {
+        transport.send(msg.to_string());
+    }
+
+    fn recv(transport: &dyn Transport) -> Value {
+        serde_json::from_str(&transport.recv_blocking().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn channel_network_routes_by_dest() {
+        let network = ChannelNetwork::new();
+        let n1 = network.connect("n1");
+        let n2 = network.connect("n2");
+        send(
+            &n1,
+            json!({"src": "n1", "dest": "n2", "body": {"type": "ping"}}),
+        );
+        send(
+            &n1,
+            json!({"src": "n1", "dest": "n3", "body": {"type": "lost"}}),
+        );
+        send(
+            &n2,
+            json!({"src": "n2", "dest": "n1", "body": {"type": "pong"}}),
+        );
+        assert_eq!(recv(&n2)["body"]["type"], "ping");
+        assert_eq!(recv(&n1)["body"]["type"], "pong");
+    }
+
+    #[test]
+    fn echo_workload_in_process() {
+        let network = ChannelNetwork::new();
+        let node = Arc::new(network.connect("n1"));
+        let client = network.connect("c1");
+        thread::spawn(move || {
+            crate::io::set_transport(node);
+            crate::workloads::echo::run();
+        });
+        send(
+            &client,
+            json!({"src": "c1", "dest": "n1", "body": {"type": "init", "msg_id": 1, "node_id": "n1", "node_ids": ["n1"]}}),
+        );
+        assert_eq!(recv(&client)["body"]["type"], "init_ok");
+        send(
+            &client,
+            json!({"src": "c1", "dest": "n1", "body": {"type": "echo", "msg_id": 2, "echo": "hello"}}),
+        );
+        let resp = recv(&client);
+        assert_eq!(resp["body"]["type"], "echo_ok");
+        assert_eq!(resp["body"]["in_reply_to"], 2);
+        assert_eq!(resp["body"]["echo"], "hello");
+    }
+
+    #[test]
+    fn tcp_replies_over_incoming_connection() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let n1 = TcpTransport::bind(localhost, HashMap::new()).unwrap();
+        let c1 = TcpTransport::bind(
+            localhost,
+            HashMap::from([("n1".to_owned(), n1.local_addr())]),
+        )
+        .unwrap();
+        send(
+            &c1,
+            json!({"src": "c1", "dest": "n1", "body": {"type": "ping"}}),
+        );
+        assert_eq!(recv(&n1)["body"]["type"], "ping");
+        send(
+            &n1,
+            json!({"src": "n1", "dest": "c1", "body": {"type": "pong"}}),
+        );
+        assert_eq!(recv(&c1)["body"]["type"], "pong");
+    }
+
+    #[test]
+    fn tcp_peer_not_reading_does_not_block_others() {
+        let localhost = "127.0.0.1:0".parse().unwrap();
+        let stuck = TcpListener::bind(localhost).unwrap();
+        let n2 = TcpTransport::bind(localhost, HashMap::new()).unwrap();
+        let n1 = TcpTransport::bind(
+            localhost,
+            HashMap::from([
+                ("stuck".to_owned(), stuck.local_addr().unwrap()),
+                ("n2".to_owned(), n2.local_addr()),
+            ]),
+        )
+        .unwrap();
+        let data = "x".repeat(1 << 20);
+        for _ in 0..32 {
+            send(
+                &n1,
+                json!({"src": "n1", "dest": "stuck", "body": {"type": "fill", "data": data}}),
+            );
+        }
+        send(
+            &n1,
+            json!({"src": "n1", "dest": "n2", "body": {"type": "ping"}}),
+        );
+        assert_eq!(recv(&n2)["body"]["type"], "ping");
+    }
+}
@@ -0,0 +1,216 @@
+//! Leveled logging to stderr, every line is tagged with the node id and the
+//! message being handled.
+//!
+//! Filters are read from `RUST_LOG` (e.g. `info,demo::io=debug` also logs every
+//! sent and received message), `DEMO_LOG_FORMAT=json` switches to one JSON
+//! object per line.
+
+use std::{cell::RefCell, future::Future, io::Write};
+
+use log::LevelFilter;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::protocol::{Message, MessageId, NodeId, RawMessage};
+
+const FORMAT_ENV: &str = "DEMO_LOG_FORMAT";
+
+thread_local! {
+    static NODE_ID: RefCell<Option<NodeId>> = const { RefCell::new(None) };
+}
+
+tokio::task_local! {
+    static SPAN: MsgSpan;
+}
+
+pub fn init() {
+    let json = std::env::var(FORMAT_ENV).is_ok_and(|format| format == "json");
+    env_logger::Builder::new()
+        .filter_level(LevelFilter::Info)
+        .parse_default_env()
+        .format(move |buf, record| {
+            let ctx = LineContext::current();
+            let ts = buf.timestamp_millis().to_string();
+            let msg = record.args().to_string();
+            if json {
+                let line = ctx.to_json(&ts, record.level().as_str(), record.target(), &msg);
+                writeln!(buf, "{line}")
+            } else {
+                writeln!(
+                    buf,
+                    "{ts} {:<5} {}{}: {msg}",
+                    record.level(),
+                    ctx.text_prefix(),
+                    record.target()
+                )
+            }
+        })
+        .init();
+}
+
+/// Sets the node id logged by the node running on the current thread.
+pub fn set_node_id(node_id: &str) {
+    NODE_ID.with(|id| *id.borrow_mut() = Some(node_id.to_owned()));
+}
+
+/// The message being handled, attached to every line logged while handling it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsgSpan {
+    pub src: NodeId,
+    pub msg_id: Option<MessageId>,
+    pub msg_type: Option<String>,
+}
+
+/// The fields of a received line the span needs, the rest is skipped.
+#[derive(Deserialize)]
+struct SpanHeader {
+    src: NodeId,
+    body: SpanBody,
+}
+
+#[derive(Deserialize)]
+struct SpanBody {
+    msg_id: Option<MessageId>,
+    #[serde(rename = "type")]
+    msg_type: Option<String>,
+}
+
+impl MsgSpan {
+    /// Encodes the body again to find its type, only for small bodies.
+    pub fn of<T: Serialize>(msg: &Message<T>) -> Self {
+        let msg_type = serde_json::to_value(&msg.body.data)
+            .ok()
+            .and_then(|data| Some(data.get("type")?.as_str()?.to_owned()));
+        Self {
+            src: msg.src.clone(),
+            msg_id: msg.body.msg_id,
+            msg_type,
+        }
+    }
+
+    pub fn of_raw(msg: &RawMessage) -> Self {
+        Self {
+            src: msg.src.clone(),
+            msg_id: msg.body.msg_id,
+            msg_type: msg.msg_type().map(str::to_owned),
+        }
+    }
+
+    pub fn of_line(line: &str) -> serde_json::Result<Self> {
+        let header: SpanHeader = serde_json::from_str(line)?;
+        Ok(Self {
+            src: header.src,
+            msg_id: header.body.msg_id,
+            msg_type: header.body.msg_type,
+        })
+    }
+
+    /// Runs the future within the span. Spawned tasks don't inherit it.
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        SPAN.scope(self, fut).await
+    }
+
+    pub fn sync_scope<R>(self, f: impl FnOnce() -> R) -> R {
+        SPAN.sync_scope(self, f)
+    }
+}
+
+struct LineContext {
+    node_id: Option<NodeId>,
+    span: Option<MsgSpan>,
+}
+
+impl LineContext {
+    fn current() -> Self {
+        Self {
+            node_id: NODE_ID.with(|id| id.borrow().clone()),
+            span: SPAN.try_with(|span| span.clone()).ok(),
+        }
+    }
+
+    fn text_prefix(&self) -> String {
+        let mut prefix = String::new();
+        if let Some(ref node_id) = self.node_id {
+            prefix.push_str(&format!("{node_id} "));
+        }
+        if let Some(ref span) = self.span {
+            prefix.push_str(&format!(
+                "[{} #{} from {}] ",
+                span.msg_type.as_deref().unwrap_or("?"),
+                span.msg_id.map_or("-".to_owned(), |id| id.to_string()),
+                span.src
+            ));
+        }
+        prefix
+    }
+
+    fn to_json(&self, ts: &str, level: &str, target: &str, msg: &str) -> Value {
+        let span = self.span.as_ref();
+        json!({
+            "ts": ts,
+            "level": level,
+            "target": target,
+            "node": self.node_id,
+            "src": span.map(|s| &s.src),
+            "msg_id": span.and_then(|s| s.msg_id),
+            "type": span.and_then(|s| s.msg_type.as_ref()),
+            "msg": msg,
+        })
+    }
+}
+
+#[cfg(test)]
+mod logging_tests {
+    use serde_json::json;
+
+    use super::{set_node_id, LineContext, MsgSpan};
+    use crate::protocol::{echo, Body, Message};
+
+    fn echo_msg() -> echo::Message {
+        Message {
+            src: "c1".to_owned(),
+            dest: "n1".to_owned(),
+            body: Body {
+                msg_id: Some(7),
+                in_reply_to: None,
+                data: echo::BodyData::InitOk,
+            },
+        }
+    }
+
+    #[test]
+    fn span_of_message() {
+        let expected = MsgSpan {
+            src: "c1".to_owned(),
+            msg_id: Some(7),
+            msg_type: Some("init_ok".to_owned()),
+        };
+        assert_eq!(MsgSpan::of(&echo_msg()), expected);
+        assert_eq!(MsgSpan::of_raw(&echo_msg().to_raw().unwrap()), expected);
+        let line = serde_json::to_string(&echo_msg()).unwrap();
+        assert_eq!(MsgSpan::of_line(&line).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn context_within_span() {
+        set_node_id("n1");
+        let outside = LineContext::current();
+        assert_eq!(outside.text_prefix(), "n1 ");
+        let line = MsgSpan::of(&echo_msg())
+            .scope(async { LineContext::current().to_json("0", "INFO", "demo", "hi") })
+            .await;
+        assert_eq!(
+            line,
+            json!({
+                "ts": "0",
+                "level": "INFO",
+                "target": "demo",
+                "node": "n1",
+                "src": "c1",
+                "msg_id": 7,
+                "type": "init_ok",
+                "msg": "hi",
+            })
+        );
+    }
+}
@@ -1,26 +1,57 @@
+mod cli;
 mod io;
+mod logging;
 mod protocol;
 mod raft;
 mod workloads;
 
+use cli::{CliError, Command, Options};
+use workloads::WORKLOADS;
+
 fn main() {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Info)
-        .init();
+    logging::init();
     let args: Vec<String> = std::env::args().skip(1).collect();
-    if args.len() != 1 {
-        panic!("Expected single argument, got {:?}", args);
+    if let Err(err) = run(&args) {
+        eprintln!("Error: {err}\n");
+        eprintln!("Usage: demo <workload> [--option value]...\n       demo list");
+        std::process::exit(2);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), CliError> {
+    match cli::parse_command(args)? {
+        Command::List => {
+            print_workloads();
+            Ok(())
+        }
+        Command::Run { workload, args } => {
+            let workload =
+                workloads::find(&workload).ok_or(CliError::UnknownWorkload(workload))?;
+            let options = Options::parse(workload.options(), &args)?;
+            io::set_transport(io::transport::from_env());
+            workload.run(&options)
+        }
     }
-    match args[0].as_str() {
-        "echo" => workloads::echo::run(),
-        "broadcast" => workloads::broadcast::run(),
-        "g-set" => workloads::crdts::g_set::run(),
-        "g-counter" => workloads::crdts::g_counter::run(),
-        "pn-counter" => workloads::crdts::pn_counter::run(),
-        "txn-list-append-single-node" => workloads::txn_list_append::single_node::run(),
-        "txn-list-append-shared-state" => workloads::txn_list_append::shared_state::run(),
-        "txn-list-append-splitted-state" => workloads::txn_list_append::splitted_state::run(),
-        "lin-kv-single-node" => workloads::lin_kv::single_node::run(),
-        other => panic!("Unknown workload '{}'", other),
+}
+
+fn print_workloads() {
+    for workload in WORKLOADS {
+        match workload.maelstrom_workload() {
+            Some(maelstrom) => println!(
+                "{:<32}{} (maelstrom workload {maelstrom})",
+                workload.name(),
+                workload.description()
+            ),
+            None => println!("{:<32}{}", workload.name(), workload.description()),
+        }
+        for option in workload.options() {
+            println!(
+                "    --{:<26}{} (default {}, env {})",
+                option.name,
+                option.description,
+                option.default,
+                option.env_var()
+            );
+        }
     }
 }
@@ -1,12 +1,21 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use serde::{self, Deserialize, Serialize};
+use serde_json::Value;
 
 use super::{InitData, NodeId};
 
-pub type BroadcastValue = i32;
+/// Any JSON value, Maelstrom sends integers by default.
+pub type BroadcastValue = Value;
 pub type Message = super::Message<BodyData>;
 
+/// Digest of the values falling into one hash bucket.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketDigest {
+    pub count: u64,
+    pub hash: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
@@ -21,8 +30,23 @@ pub enum BodyData {
         message: BroadcastValue,
     },
     BroadcastOk,
+    /// Batch of values between nodes in the gossip mode.
+    Gossip {
+        messages: Vec<BroadcastValue>,
+    },
+    GossipOk,
+    /// Anti-entropy digest of the sender values.
+    Sync {
+        digest: Vec<BucketDigest>,
+    },
+    /// Values of the buckets that differ from the digest.
+    SyncOk {
+        buckets: Vec<usize>,
+        messages: Vec<BroadcastValue>,
+    },
     Read,
     ReadOk {
-        messages: Vec<BroadcastValue>,
+        /// Shared with the node state, so a read doesn't copy the values.
+        messages: Arc<Vec<BroadcastValue>>,
     },
 }
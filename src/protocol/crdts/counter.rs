@@ -1,5 +1,7 @@
 use serde::{self, Deserialize, Serialize};
 
+use crate::protocol::ErrorData;
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
@@ -12,4 +14,5 @@ pub enum CounterBodyData<T> {
     ReadOk{
         value: T,
     },
+    Error(ErrorData),
 }
\ No newline at end of file
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use serde::{self, Deserialize, Serialize};
+use serde_json::Value;
+
+use super::counter::CounterBodyData;
+use super::or_set::OrSetBodyData;
+use super::register::{RegisterBodyData, RegisterValue};
+use crate::protocol::ErrorData;
+
+pub type MapKey = String;
+
+/// Message to or from the CRDT stored under a key, `crdt` picks its kind.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "crdt", content = "body", rename_all = "kebab-case")]
+pub enum NestedBody {
+    GCounter(CounterBodyData<u64>),
+    PnCounter(CounterBodyData<i64>),
+    OrSet(OrSetBodyData),
+    LwwRegister(RegisterBodyData<Option<RegisterValue>>),
+}
+
+impl NestedBody {
+    pub fn is_read(&self) -> bool {
+        matches!(
+            self,
+            NestedBody::GCounter(CounterBodyData::Read)
+                | NestedBody::PnCounter(CounterBodyData::Read)
+                | NestedBody::OrSet(OrSetBodyData::Read)
+                | NestedBody::LwwRegister(RegisterBodyData::Read)
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum MapBodyData {
+    Apply {
+        key: MapKey,
+        #[serde(flatten)]
+        body: NestedBody,
+    },
+    ApplyOk {
+        key: MapKey,
+        #[serde(flatten)]
+        body: NestedBody,
+    },
+    Read,
+    /// What a read of every key returns.
+    ReadOk {
+        value: BTreeMap<MapKey, Value>,
+    },
+    Error(ErrorData),
+}
+
+#[cfg(test)]
+mod map_tests {
+    use serde_json::json;
+
+    use super::{MapBodyData, NestedBody};
+    use crate::protocol::crdts::counter::CounterBodyData;
+
+    #[test]
+    fn nests_the_value_body() {
+        let raw = json!({"type": "apply", "key": "visits", "crdt": "g-counter", "body": {"type": "add", "delta": 2}});
+        let body: MapBodyData = serde_json::from_value(raw.clone()).unwrap();
+        assert!(matches!(
+            body,
+            MapBodyData::Apply {
+                ref key,
+                body: NestedBody::GCounter(CounterBodyData::Add { delta: 2 }),
+            } if key == "visits"
+        ));
+        assert_eq!(serde_json::to_value(&body).unwrap(), raw);
+    }
+}
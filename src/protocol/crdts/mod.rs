@@ -2,15 +2,26 @@ use serde::{self, Deserialize, Serialize};
 
 pub mod g_set;
 pub mod counter;
+pub mod map;
+pub mod or_set;
+pub mod register;
 
-pub type Message<T, S> = crate::protocol::Message<CrdtBody<T, S>>;
+/// Messages handled by the CRDT node itself, the rest go to the CRDT.
+pub const COMMON_MSG_TYPES: [&str; 7] = [
+    "init",
+    "init_ok",
+    "replicate",
+    "replicate_delta",
+    "replicate_ok",
+    "replicate_digest",
+    "replicate_pull",
+];
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum CrdtBody<T, S> {
-    Common(CommonBodyData<S>),
-    Custom(T),
-}
+/// Number of local changes made by the sending node.
+pub type ReplicationVersion = u64;
+
+/// Hash of a full state, equal on nodes with equal states.
+pub type StateDigest = u64;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -20,5 +31,24 @@ pub enum CommonBodyData<S> {
     InitOk,
     Replicate {
         state: S,
+        /// Only set when the state stands in for dropped deltas, which needs an ack.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        version: Option<ReplicationVersion>,
+    },
+    /// Local changes of the sender, each one merged like a full state.
+    ReplicateDelta {
+        deltas: Vec<S>,
+        version: ReplicationVersion,
+    },
+    ReplicateOk {
+        version: ReplicationVersion,
     },
-}
\ No newline at end of file
+    /// Push-pull round, answered with `ReplicatePull` if the states differ.
+    ReplicateDigest {
+        digest: StateDigest,
+    },
+    /// State of the sender, answered with the merged state in `Replicate`.
+    ReplicatePull {
+        state: S,
+    },
+}
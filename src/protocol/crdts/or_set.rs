@@ -0,0 +1,15 @@
+use serde::{self, Deserialize, Serialize};
+
+pub type ElementValue = i32;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum OrSetBodyData {
+    Add { element: ElementValue },
+    AddOk,
+    Remove { element: ElementValue },
+    RemoveOk,
+    Read,
+    ReadOk { value: Vec<ElementValue> },
+}
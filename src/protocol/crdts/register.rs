@@ -0,0 +1,16 @@
+use serde::{self, Deserialize, Serialize};
+use serde_json::Value;
+
+pub type RegisterValue = Value;
+
+/// `R` is what a read returns, the LWW-register has at most one value and the
+/// MV-register all concurrently written ones.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum RegisterBodyData<R> {
+    Write { value: RegisterValue },
+    WriteOk,
+    Read,
+    ReadOk { value: R },
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use serde::{self, Deserialize, Serialize};
+use serde_json::Value;
+
+pub type LogKey = String;
+pub type Offset = u64;
+pub type Message = super::Message<BodyData>;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum BodyData {
+    Init(super::InitData),
+    InitOk,
+    Send {
+        key: LogKey,
+        msg: Value,
+    },
+    SendOk {
+        offset: Offset,
+    },
+    Poll {
+        offsets: HashMap<LogKey, Offset>,
+    },
+    PollOk {
+        /// `[offset, msg]` pairs in offset order, starting at the polled offset.
+        msgs: HashMap<LogKey, Vec<(Offset, Value)>>,
+    },
+    CommitOffsets {
+        offsets: HashMap<LogKey, Offset>,
+    },
+    CommitOffsetsOk,
+    ListCommittedOffsets {
+        keys: Vec<LogKey>,
+    },
+    ListCommittedOffsetsOk {
+        offsets: HashMap<LogKey, Offset>,
+    },
+    // Replies of the storage services, see `super::kv`.
+    ReadOk {
+        value: Value,
+    },
+    WriteOk,
+    CasOk,
+    Error(super::ErrorData),
+}
@@ -0,0 +1,41 @@
+use serde::{self, Deserialize, Serialize};
+use serde_json::Value;
+
+pub type Message = super::Message<BodyData>;
+
+pub const LIN_KV_SERVICE: &str = "lin-kv";
+#[allow(dead_code)]
+pub const SEQ_KV_SERVICE: &str = "seq-kv";
+pub const LWW_KV_SERVICE: &str = "lww-kv";
+#[allow(dead_code)]
+pub const LIN_TSO_SERVICE: &str = "lin-tso";
+
+/// Requests and responses of the Maelstrom provided services.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum BodyData {
+    Read {
+        key: Value,
+    },
+    ReadOk {
+        value: Value,
+    },
+    Write {
+        key: Value,
+        value: Value,
+    },
+    WriteOk,
+    Cas {
+        key: Value,
+        from: Value,
+        to: Value,
+        create_if_not_exists: bool,
+    },
+    CasOk,
+    Ts,
+    TsOk {
+        ts: u64,
+    },
+    Error(super::ErrorData),
+}
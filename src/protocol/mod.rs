@@ -1,12 +1,17 @@
-use std::sync::atomic::{AtomicU64, Ordering};
+use std::cell::Cell;
 
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 pub mod broadcast;
 pub mod crdts;
 pub mod txn_list_append;
+pub mod txn_rw_register;
 pub mod echo;
+pub mod kafka;
+pub mod kv;
 pub mod link_kv;
+pub mod unique_ids;
 
 pub type MessageId = u64;
 pub type NodeId = String;
@@ -26,16 +31,71 @@ pub struct InitData {
     pub node_ids: Vec<NodeId>,
 }
 
-#[derive(Debug, PartialEq, Eq, serde_repr::Serialize_repr, serde_repr::Deserialize_repr)]
-#[repr(u8)]
+/// Error codes defined by Maelstrom, see `doc/protocol.md` in the Maelstrom repo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "u32", into = "u32")]
 pub enum ErrorCode {
-    Timeout = 0,
-    TemporarilyUnavailable = 11,
-    Crash = 13,
-    Abort = 14,
-    KeyDoesNotExist = 20,
-    PreconditionFailed = 22,
-    TxnConflict = 30,
+    Timeout,
+    NodeNotFound,
+    NotSupported,
+    TemporarilyUnavailable,
+    MalformedRequest,
+    Crash,
+    Abort,
+    KeyDoesNotExist,
+    KeyAlreadyExists,
+    PreconditionFailed,
+    TxnConflict,
+    /// Codes not defined by Maelstrom, custom ones start from 1000.
+    Other(u32),
+}
+
+impl ErrorCode {
+    /// Definite errors guarantee the request had no effect, so it's safe to retry.
+    pub fn is_definite(&self) -> bool {
+        !matches!(
+            self,
+            ErrorCode::Timeout | ErrorCode::Crash | ErrorCode::Other(_)
+        )
+    }
+}
+
+impl From<u32> for ErrorCode {
+    fn from(code: u32) -> Self {
+        match code {
+            0 => ErrorCode::Timeout,
+            1 => ErrorCode::NodeNotFound,
+            10 => ErrorCode::NotSupported,
+            11 => ErrorCode::TemporarilyUnavailable,
+            12 => ErrorCode::MalformedRequest,
+            13 => ErrorCode::Crash,
+            14 => ErrorCode::Abort,
+            20 => ErrorCode::KeyDoesNotExist,
+            21 => ErrorCode::KeyAlreadyExists,
+            22 => ErrorCode::PreconditionFailed,
+            30 => ErrorCode::TxnConflict,
+            other => ErrorCode::Other(other),
+        }
+    }
+}
+
+impl From<ErrorCode> for u32 {
+    fn from(code: ErrorCode) -> Self {
+        match code {
+            ErrorCode::Timeout => 0,
+            ErrorCode::NodeNotFound => 1,
+            ErrorCode::NotSupported => 10,
+            ErrorCode::TemporarilyUnavailable => 11,
+            ErrorCode::MalformedRequest => 12,
+            ErrorCode::Crash => 13,
+            ErrorCode::Abort => 14,
+            ErrorCode::KeyDoesNotExist => 20,
+            ErrorCode::KeyAlreadyExists => 21,
+            ErrorCode::PreconditionFailed => 22,
+            ErrorCode::TxnConflict => 30,
+            ErrorCode::Other(code) => code,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -50,6 +110,20 @@ impl ErrorData {
     }
 }
 
+impl std::fmt::Display for ErrorData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} ({}): {}", self.code, u32::from(self.code), self.text)
+    }
+}
+
+impl std::error::Error for ErrorData {}
+
+impl From<serde_json::Error> for ErrorData {
+    fn from(err: serde_json::Error) -> Self {
+        ErrorData::new(err.to_string(), ErrorCode::MalformedRequest)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InitOkData {}
 
@@ -74,7 +148,125 @@ impl<T> Message<T> {
     }
 }
 
+impl<T: Serialize> Message<T> {
+    /// Re-decodes the message with another body type, e.g. to hand a service
+    /// reply received by a workload over to a service client.
+    pub fn convert<R: DeserializeOwned>(&self) -> serde_json::Result<Message<R>> {
+        self.to_raw()?.decode()
+    }
+
+    pub fn to_raw(&self) -> serde_json::Result<RawMessage> {
+        serde_json::from_value(serde_json::to_value(self)?)
+    }
+}
+
+/// Body fields other than `msg_id` and `in_reply_to`, including `type`.
+pub type RawBody = Map<String, Value>;
+
+/// Message with an undecoded body, for routing by `type` or `in_reply_to`
+/// before picking the body enum, or for passing messages through as is.
+/// `msg_id` and `in_reply_to` are decoded as usual.
+pub type RawMessage = Message<RawBody>;
+
+impl RawMessage {
+    pub fn msg_type(&self) -> Option<&str> {
+        self.body.data.get("type").and_then(Value::as_str)
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self) -> serde_json::Result<Message<T>> {
+        Ok(Message {
+            src: self.src.clone(),
+            dest: self.dest.clone(),
+            body: Body {
+                msg_id: self.body.msg_id,
+                in_reply_to: self.body.in_reply_to,
+                data: serde_json::from_value(Value::Object(self.body.data.clone()))?,
+            },
+        })
+    }
+}
+
+/// Message ids are unique per node. Every node runs on its own thread
+/// (see `io::set_transport`), so the counter is thread local.
 pub fn gen_next_msg_id() -> MessageId {
-    static COUNTER: AtomicU64 = AtomicU64::new(0);
-    COUNTER.fetch_add(1, Ordering::Relaxed)
+    thread_local! {
+        static NEXT_MSG_ID: Cell<MessageId> = const { Cell::new(0) };
+    }
+    NEXT_MSG_ID.with(|next| next.replace(next.get() + 1))
+}
+
+#[cfg(test)]
+mod error_code_serde_tests {
+    use super::{ErrorCode, ErrorData};
+    use serde_json::json;
+
+    #[test]
+    fn known_codes() {
+        for code in [0, 1, 10, 11, 12, 13, 14, 20, 21, 22, 30] {
+            let parsed: ErrorCode = serde_json::from_value(json!(code)).unwrap();
+            assert!(!matches!(parsed, ErrorCode::Other(_)), "{code}");
+            assert_eq!(serde_json::to_value(parsed).unwrap(), json!(code));
+        }
+    }
+
+    #[test]
+    fn custom_code() {
+        let data: ErrorData =
+            serde_json::from_value(json!({"code": 1005, "text": "custom"})).unwrap();
+        assert_eq!(data.code, ErrorCode::Other(1005));
+        assert!(!data.code.is_definite());
+        assert_eq!(serde_json::to_value(data.code).unwrap(), json!(1005));
+    }
+
+    #[test]
+    fn definite_codes() {
+        assert!(ErrorCode::KeyDoesNotExist.is_definite());
+        assert!(ErrorCode::TxnConflict.is_definite());
+        assert!(!ErrorCode::Timeout.is_definite());
+        assert!(!ErrorCode::Crash.is_definite());
+    }
+}
+
+#[cfg(test)]
+mod raw_message_tests {
+    use super::{echo, RawMessage};
+    use serde_json::json;
+
+    fn raw_echo() -> RawMessage {
+        serde_json::from_value(json!({
+            "src": "c1",
+            "dest": "n1",
+            "body": {"type": "echo", "msg_id": 3, "echo": "hi", "extra": [1]}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn peeks_envelope() {
+        let msg = raw_echo();
+        assert_eq!(msg.msg_type(), Some("echo"));
+        assert_eq!(msg.body.msg_id, Some(3));
+        assert_eq!(msg.body.in_reply_to, None);
+    }
+
+    #[test]
+    fn decodes_typed_body() {
+        let msg: echo::Message = raw_echo().decode().unwrap();
+        assert!(
+            matches!(msg.body.data, echo::BodyData::Echo(ref data) if data.echo == json!("hi"))
+        );
+        assert_eq!(msg.body.msg_id, Some(3));
+        assert!(raw_echo().decode::<super::kv::BodyData>().is_err());
+    }
+
+    #[test]
+    fn passes_through_unchanged() {
+        let value = serde_json::to_value(raw_echo()).unwrap();
+        assert_eq!(value["body"]["extra"], json!([1]));
+        assert_eq!(value["body"]["type"], json!("echo"));
+        assert_eq!(
+            serde_json::to_value(raw_echo().to_raw().unwrap()).unwrap(),
+            value
+        );
+    }
 }
@@ -5,9 +5,6 @@ pub type KeyValue = u64;
 pub type ElementValue = u64;
 pub type Message = super::Message<BodyData>;
 
-pub const LIN_KV_SERVICE: &str = "lin-kv";
-pub const LWW_KV_SERVICE: &str = "lww-kv";
-
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
@@ -16,23 +13,11 @@ pub enum BodyData {
     InitOk,
     Txn(TxnData),
     TxnOk(TxnData),
-    Read {
-        key: Value,
-    },
+    // Replies of the storage services, see `super::kv`.
     ReadOk {
         value: Value,
     },
-    Write {
-        key: Value,
-        value: Value,
-    },
     WriteOk,
-    Cas {
-        key: Value,
-        from: Value,
-        to: Value,
-        create_if_not_exists: bool,
-    },
     CasOk,
     Error(super::ErrorData),
 }
@@ -0,0 +1,129 @@
+use serde::{self, Deserialize, Serialize};
+
+use super::NodeId;
+
+pub type KeyValue = u64;
+pub type RegisterValue = u64;
+pub type Message = super::Message<BodyData>;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum BodyData {
+    Init(super::InitData),
+    InitOk,
+    Txn(TxnData),
+    TxnOk(TxnData),
+    Replicate { writes: Vec<VersionedWrite> },
+    ReplicateOk,
+    Error(super::ErrorData),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TxnData {
+    pub txn: Vec<TxnFunc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "TxnFuncRepr")]
+#[serde(try_from = "TxnFuncRepr")]
+pub enum TxnFunc {
+    Read {
+        key: KeyValue,
+        value: Option<RegisterValue>,
+    },
+    Write {
+        key: KeyValue,
+        value: RegisterValue,
+    },
+}
+
+/// Lamport timestamp of a write, ties are broken by the writing node so that
+/// every node orders the writes of a key the same way.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Version {
+    pub counter: u64,
+    pub node_id: NodeId,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionedWrite {
+    pub key: KeyValue,
+    pub value: RegisterValue,
+    pub version: Version,
+}
+
+const READ_FUNC_REPR: &str = "r";
+const WRITE_FUNC_REPR: &str = "w";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TxnFuncRepr(String, KeyValue, Option<RegisterValue>);
+
+impl TryFrom<TxnFuncRepr> for TxnFunc {
+    type Error = std::io::Error;
+
+    fn try_from(repr: TxnFuncRepr) -> Result<Self, Self::Error> {
+        let TxnFuncRepr(f, key, v) = repr;
+        match (f.as_str(), v) {
+            (READ_FUNC_REPR, value) => Ok(Self::Read { key, value }),
+            (WRITE_FUNC_REPR, Some(value)) => Ok(Self::Write { key, value }),
+            (f, v) => Err(std::io::Error::other(format!(
+                "Invalid repr: [{f}, {key}, {v:?}]'"
+            ))),
+        }
+    }
+}
+
+impl From<TxnFunc> for TxnFuncRepr {
+    fn from(val: TxnFunc) -> Self {
+        match val {
+            TxnFunc::Read { key, value } => TxnFuncRepr(READ_FUNC_REPR.to_string(), key, value),
+            TxnFunc::Write { key, value } => {
+                TxnFuncRepr(WRITE_FUNC_REPR.to_string(), key, Some(value))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod txn_func_serde_tests {
+    use super::TxnFunc;
+    use serde_json::json;
+
+    #[test]
+    fn empty_read() {
+        check(
+            TxnFunc::Read {
+                key: 4,
+                value: None,
+            },
+            json!(["r", 4, null]),
+        );
+    }
+
+    #[test]
+    fn value_read() {
+        check(
+            TxnFunc::Read {
+                key: 4,
+                value: Some(2),
+            },
+            json!(["r", 4, 2]),
+        );
+    }
+
+    #[test]
+    fn write() {
+        check(TxnFunc::Write { key: 2, value: 1 }, json!(["w", 2, 1]));
+    }
+
+    #[test]
+    fn write_without_value() {
+        assert!(serde_json::from_value::<TxnFunc>(json!(["w", 2, null])).is_err());
+    }
+
+    fn check(f: TxnFunc, expected: serde_json::Value) {
+        assert_eq!(serde_json::to_value(f.clone()).unwrap(), expected);
+        assert_eq!(serde_json::from_value::<TxnFunc>(expected).unwrap(), f);
+    }
+}
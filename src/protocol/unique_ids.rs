@@ -0,0 +1,17 @@
+use serde::{self, Deserialize, Serialize};
+use serde_json::Value;
+
+pub type Message = super::Message<BodyData>;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum BodyData {
+    Init(super::InitData),
+    InitOk,
+    Generate,
+    GenerateOk {
+        /// Any JSON value unique across the cluster.
+        id: Value,
+    },
+}
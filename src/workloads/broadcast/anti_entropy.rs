@@ -0,0 +1,93 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use crate::protocol::broadcast::BucketDigest;
+
+const BUCKETS: usize = 16;
+
+/// Order independent digest of a value set, split into hash buckets so that
+/// reconciliation only exchanges the values of the differing buckets.
+pub struct Digest {
+    buckets: Vec<BucketDigest>,
+}
+
+impl Default for Digest {
+    fn default() -> Self {
+        Self {
+            buckets: vec![BucketDigest::default(); BUCKETS],
+        }
+    }
+}
+
+impl Digest {
+    /// Must be called once per distinct value.
+    pub fn insert<T: Hash>(&mut self, value: &T) {
+        let hash = value_hash(value);
+        let bucket = &mut self.buckets[bucket_of(hash)];
+        bucket.count += 1;
+        bucket.hash = bucket.hash.wrapping_add(hash);
+    }
+
+    pub fn buckets(&self) -> &[BucketDigest] {
+        &self.buckets
+    }
+
+    pub fn differing(&self, other: &[BucketDigest]) -> Vec<usize> {
+        (0..BUCKETS)
+            .filter(|&i| other.get(i) != Some(&self.buckets[i]))
+            .collect()
+    }
+}
+
+pub fn in_buckets<T: Hash>(value: &T, buckets: &[usize]) -> bool {
+    buckets.contains(&bucket_of(value_hash(value)))
+}
+
+fn value_hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn bucket_of(hash: u64) -> usize {
+    (hash % BUCKETS as u64) as usize
+}
+
+#[cfg(test)]
+mod anti_entropy_tests {
+    use std::collections::HashSet;
+
+    use super::{in_buckets, Digest};
+
+    fn digest(values: &HashSet<i32>) -> Digest {
+        let mut digest = Digest::default();
+        values.iter().for_each(|v| digest.insert(v));
+        digest
+    }
+
+    #[test]
+    fn equal_sets_in_any_order() {
+        let a: HashSet<_> = (0..100).collect();
+        let b: HashSet<_> = (0..100).rev().collect();
+        assert!(digest(&a).differing(digest(&b).buckets()).is_empty());
+    }
+
+    #[test]
+    fn reconciles_differing_buckets() {
+        let mut a: HashSet<_> = (0..100).collect();
+        let mut b: HashSet<_> = (50..150).collect();
+        let buckets = digest(&b).differing(digest(&a).buckets());
+        let from_b: Vec<_> = b.iter().filter(|v| in_buckets(*v, &buckets)).copied().collect();
+        let to_b: Vec<_> = a
+            .iter()
+            .filter(|v| in_buckets(*v, &buckets) && !from_b.contains(v))
+            .copied()
+            .collect();
+        a.extend(from_b);
+        b.extend(to_b);
+        assert_eq!(a, b);
+        assert!(digest(&a).differing(digest(&b).buckets()).is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use tokio::time::{Duration, Instant};
+
+use super::store::{ValueStore, Version};
+use crate::protocol::{broadcast::BroadcastValue, MessageId, NodeId};
+
+/// Unacknowledged messages remembered per neighbour, the oldest are dropped
+/// first as the retransmissions carry their values too.
+const MAX_IN_FLIGHT: usize = 32;
+
+/// Values sent to every neighbour and not acknowledged yet. Every neighbour
+/// has a single retransmission deadline, all its unacknowledged values are
+/// resent in one batch once it passes.
+pub struct FloodState {
+    retry_interval: Duration,
+    peers: HashMap<NodeId, Peer>,
+}
+
+#[derive(Default)]
+struct Peer {
+    unacked: BTreeSet<Version>,
+    in_flight: BTreeMap<MessageId, Vec<Version>>,
+    retry_at: Option<Instant>,
+}
+
+impl Peer {
+    fn send(&mut self, msg_id: MessageId, versions: Vec<Version>) {
+        self.in_flight.insert(msg_id, versions);
+        while self.in_flight.len() > MAX_IN_FLIGHT {
+            self.in_flight.pop_first();
+        }
+    }
+
+    fn remove(&mut self, versions: &[Version]) {
+        for version in versions {
+            self.unacked.remove(version);
+        }
+        if self.unacked.is_empty() {
+            self.in_flight.clear();
+            self.retry_at = None;
+        }
+    }
+}
+
+impl FloodState {
+    pub fn new(neighbours: &[NodeId], retry_interval: Duration) -> Self {
+        Self {
+            retry_interval,
+            peers: neighbours
+                .iter()
+                .map(|node_id| (node_id.clone(), Peer::default()))
+                .collect(),
+        }
+    }
+
+    pub fn sent(&mut self, to: &str, msg_id: MessageId, versions: Vec<Version>, now: Instant) {
+        let Some(peer) = self.peers.get_mut(to) else {
+            return;
+        };
+        peer.unacked.extend(&versions);
+        peer.send(msg_id, versions);
+        peer.retry_at.get_or_insert(now + self.retry_interval);
+    }
+
+    pub fn acked(&mut self, from: &str, msg_id: MessageId) {
+        if let Some(peer) = self.peers.get_mut(from) {
+            if let Some(versions) = peer.in_flight.remove(&msg_id) {
+                peer.remove(&versions);
+            }
+        }
+    }
+
+    /// The neighbour sent these values, so it doesn't need them.
+    pub fn received(&mut self, from: &str, versions: &[Version]) {
+        if let Some(peer) = self.peers.get_mut(from) {
+            peer.remove(versions);
+        }
+    }
+
+    /// One batch of all unacknowledged values for every neighbour past its
+    /// deadline, acks of earlier messages to it still count.
+    pub fn retransmissions(
+        &mut self,
+        store: &ValueStore,
+        now: Instant,
+        mut gen_msg_id: impl FnMut() -> MessageId,
+    ) -> Vec<(NodeId, MessageId, Vec<BroadcastValue>)> {
+        let mut batches = Vec::new();
+        for (node_id, peer) in self.peers.iter_mut() {
+            if peer.retry_at.is_none_or(|at| at > now) {
+                continue;
+            }
+            let msg_id = gen_msg_id();
+            let versions: Vec<_> = peer.unacked.iter().copied().collect();
+            let batch = versions.iter().map(|&v| store.get(v).clone()).collect();
+            peer.send(msg_id, versions);
+            peer.retry_at = Some(now + self.retry_interval);
+            batches.push((node_id.clone(), msg_id, batch));
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod flood_tests {
+    use serde_json::json;
+    use tokio::time::{Duration, Instant};
+
+    use super::FloodState;
+    use crate::workloads::broadcast::store::ValueStore;
+
+    const RETRY: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn retransmits_unacked_values_in_one_batch() {
+        let mut store = ValueStore::default();
+        let mut state = FloodState::new(&["n2".to_owned(), "n3".to_owned()], RETRY);
+        let now = Instant::now();
+        for (msg_id, value) in [(1, 10), (2, 20), (3, 30)] {
+            let version = store.insert(json!(value)).unwrap();
+            state.sent("n2", msg_id, vec![version], now);
+        }
+        state.acked("n2", 2);
+        assert!(state.retransmissions(&store, now, || 4).is_empty());
+
+        let batches = state.retransmissions(&store, now + RETRY, || 4);
+        assert_eq!(batches, vec![("n2".to_owned(), 4, vec![json!(10), json!(30)])]);
+
+        // Acks arriving after the retransmission still count.
+        state.acked("n2", 1);
+        let batches = state.retransmissions(&store, now + RETRY * 2, || 5);
+        assert_eq!(batches, vec![("n2".to_owned(), 5, vec![json!(30)])]);
+        state.acked("n2", 3);
+        assert!(state.retransmissions(&store, now + RETRY * 3, || 6).is_empty());
+        assert!(state.peers["n2"].in_flight.is_empty());
+    }
+
+    #[test]
+    fn values_from_peer_need_no_ack() {
+        let mut store = ValueStore::default();
+        let mut state = FloodState::new(&["n2".to_owned()], RETRY);
+        let now = Instant::now();
+        let version = store.insert(json!(1)).unwrap();
+        state.sent("n2", 1, vec![version], now);
+        state.received("n2", &[version]);
+        assert!(state.retransmissions(&store, now + RETRY, || 2).is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use super::store::{ValueStore, Version};
+use crate::protocol::{broadcast::BroadcastValue, MessageId, NodeId};
+
+/// Unacknowledged batches remembered per neighbour, the oldest are dropped
+/// first as an ack of a newer batch covers them anyway.
+const MAX_IN_FLIGHT: usize = 32;
+
+/// Store version every neighbour is known to have, so a batch only carries
+/// the values added since then.
+pub struct GossipState {
+    peers: HashMap<NodeId, Peer>,
+}
+
+#[derive(Default)]
+struct Peer {
+    /// The neighbour has every value below this version.
+    acked: Version,
+    /// Versions above `acked` that came from the neighbour itself.
+    received: HashSet<Version>,
+    /// Store version every unacknowledged batch was sent at.
+    in_flight: BTreeMap<MessageId, Version>,
+}
+
+impl Peer {
+    fn ack(&mut self, version: Version) {
+        self.acked = self.acked.max(version);
+        let acked = self.acked;
+        self.received.retain(|&v| v >= acked);
+        self.in_flight.retain(|_, &mut v| v > acked);
+    }
+}
+
+impl GossipState {
+    pub fn new(neighbours: &[NodeId]) -> Self {
+        Self {
+            peers: neighbours
+                .iter()
+                .map(|node_id| (node_id.clone(), Peer::default()))
+                .collect(),
+        }
+    }
+
+    /// The neighbour sent these values, so it doesn't need them back.
+    pub fn received(&mut self, from: &str, versions: impl IntoIterator<Item = Version>) {
+        if let Some(peer) = self.peers.get_mut(from) {
+            let acked = peer.acked;
+            peer.received
+                .extend(versions.into_iter().filter(|&v| v >= acked));
+        }
+    }
+
+    pub fn acked(&mut self, from: &str, msg_id: MessageId) {
+        let Some(peer) = self.peers.get_mut(from) else {
+            return;
+        };
+        if let Some(version) = peer.in_flight.remove(&msg_id) {
+            peer.ack(version);
+        }
+    }
+
+    /// Next batch for every neighbour missing some of the values, an ack of
+    /// any batch still in flight moves the neighbour's version forward.
+    pub fn next_batches(
+        &mut self,
+        store: &ValueStore,
+        mut gen_msg_id: impl FnMut() -> MessageId,
+    ) -> Vec<(NodeId, MessageId, Vec<BroadcastValue>)> {
+        let version = store.version();
+        let mut batches = Vec::new();
+        for (node_id, peer) in self.peers.iter_mut() {
+            let batch: Vec<_> = (peer.acked..version)
+                .filter(|v| !peer.received.contains(v))
+                .map(|v| store.get(v).clone())
+                .collect();
+            if batch.is_empty() {
+                peer.ack(version);
+                continue;
+            }
+            let msg_id = gen_msg_id();
+            peer.in_flight.insert(msg_id, version);
+            while peer.in_flight.len() > MAX_IN_FLIGHT {
+                peer.in_flight.pop_first();
+            }
+            batches.push((node_id.clone(), msg_id, batch));
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod gossip_tests {
+    use serde_json::json;
+
+    use super::GossipState;
+    use crate::workloads::broadcast::store::ValueStore;
+
+    #[test]
+    fn resends_until_acked() {
+        let mut state = GossipState::new(&["n2".to_owned()]);
+        let mut store = ValueStore::default();
+        store.insert(json!(1));
+        store.insert(json!(2));
+        let mut next_id = 0;
+        let mut gen = || {
+            next_id += 1;
+            next_id
+        };
+
+        let batches = state.next_batches(&store, &mut gen);
+        assert_eq!(batches.len(), 1);
+        let (_, first_id, batch) = batches.into_iter().next().unwrap();
+        assert_eq!(batch, vec![json!(1), json!(2)]);
+
+        let (_, second_id, batch) = state.next_batches(&store, &mut gen).pop().unwrap();
+        assert_ne!(first_id, second_id);
+        assert_eq!(batch, vec![json!(1), json!(2)]);
+        state.acked("n2", second_id);
+        assert!(state.next_batches(&store, &mut gen).is_empty());
+
+        store.insert(json!(3));
+        let (_, _, batch) = state.next_batches(&store, &mut gen).pop().unwrap();
+        assert_eq!(batch, vec![json!(3)]);
+    }
+
+    #[test]
+    fn acks_earlier_batch_after_next_one_was_sent() {
+        let mut state = GossipState::new(&["n2".to_owned()]);
+        let mut store = ValueStore::default();
+        store.insert(json!(1));
+        let mut next_id = 0;
+        let mut gen = || {
+            next_id += 1;
+            next_id
+        };
+
+        let (_, first_id, _) = state.next_batches(&store, &mut gen).pop().unwrap();
+        store.insert(json!(2));
+        let (_, second_id, batch) = state.next_batches(&store, &mut gen).pop().unwrap();
+        assert_eq!(batch, vec![json!(1), json!(2)]);
+
+        state.acked("n2", first_id);
+        let (_, third_id, batch) = state.next_batches(&store, &mut gen).pop().unwrap();
+        assert_eq!(batch, vec![json!(2)]);
+
+        state.acked("n2", third_id);
+        state.acked("n2", second_id);
+        assert!(state.next_batches(&store, &mut gen).is_empty());
+    }
+
+    #[test]
+    fn skips_values_received_from_peer() {
+        let mut state = GossipState::new(&["n2".to_owned(), "n3".to_owned()]);
+        let mut store = ValueStore::default();
+        let version = store.insert(json!(1)).unwrap();
+        state.received("n2", [version]);
+        state.received("n4", [version]);
+        let batches = state.next_batches(&store, || 0);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].0, "n3");
+    }
+}
@@ -0,0 +1,348 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use rand::seq::SliceRandom;
+use tokio::time::{Duration, Instant};
+
+use super::Workload;
+use crate::{
+    cli::{CliError, OptionSpec, Options},
+    io::{
+        non_blocking::{receive_msg, receive_msg_in_span},
+        send_msg,
+    },
+    logging,
+    protocol::{broadcast::*, gen_next_msg_id, Body, MessageId, NodeId},
+};
+use flood::FloodState;
+use gossip::GossipState;
+use store::{ValueStore, Version};
+use topology::Topology;
+
+mod anti_entropy;
+mod flood;
+mod gossip;
+mod store;
+mod topology;
+
+pub static OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "mode",
+        description: "`flood` sends every value on its own, `gossip` sends periodic batches",
+        default: "flood",
+    },
+    OptionSpec {
+        name: "anti-entropy-interval-ms",
+        description: "Delay between digest exchanges with a random neighbour, 0 disables them",
+        default: "2000",
+    },
+    OptionSpec {
+        name: "topology",
+        description: "Neighbours of every node: given, star, tree, ring or mesh",
+        default: "given",
+    },
+    OptionSpec {
+        name: "tree-arity",
+        description: "Number of children of every node in the tree topology",
+        default: "4",
+    },
+    OptionSpec {
+        name: "retry-interval-ms",
+        description: "Delay before resending an unacknowledged broadcast in the flood mode",
+        default: "1000",
+    },
+    OptionSpec {
+        name: "gossip-interval-ms",
+        description: "Delay between batches to every neighbour in the gossip mode",
+        default: "200",
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Flood,
+    Gossip,
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "flood" => Ok(Mode::Flood),
+            "gossip" => Ok(Mode::Gossip),
+            other => Err(format!("expected flood or gossip, got {other}")),
+        }
+    }
+}
+
+pub struct Config {
+    mode: Mode,
+    topology: Topology,
+    retry_interval: Duration,
+    gossip_interval: Duration,
+    anti_entropy_interval: Duration,
+}
+
+impl Config {
+    pub fn from_options(options: &Options) -> Result<Self, CliError> {
+        let topology: String = options.get("topology")?;
+        let topology = Topology::parse(&topology, options.get("tree-arity")?).map_err(|reason| {
+            CliError::InvalidValue {
+                option: "topology".to_owned(),
+                value: topology.clone(),
+                reason,
+            }
+        })?;
+        Ok(Self {
+            mode: options.get("mode")?,
+            topology,
+            retry_interval: options.millis("retry-interval-ms")?,
+            gossip_interval: options.millis("gossip-interval-ms")?,
+            anti_entropy_interval: options.millis("anti-entropy-interval-ms")?,
+        })
+    }
+}
+
+pub struct Broadcast;
+
+impl Workload for Broadcast {
+    fn name(&self) -> &'static str {
+        "broadcast"
+    }
+
+    fn description(&self) -> &'static str {
+        "Spreads every value to the topology neighbours, one by one or in gossip batches"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("broadcast")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(main(config));
+}
+
+struct NodeConfig {
+    node_id: NodeId,
+    neighbours: Vec<NodeId>,
+}
+
+async fn main(config: Config) {
+    log::info!("Running broadcast workload");
+    let node_config = init_node(config.topology).await;
+    let mut node = Node {
+        gossip: GossipState::new(&node_config.neighbours),
+        flood: FloodState::new(&node_config.neighbours, config.retry_interval),
+        config: node_config,
+        mode: config.mode,
+        values: ValueStore::default(),
+    };
+    let mut gossip_timer = tokio::time::interval(config.gossip_interval);
+    let mut retry_timer = tokio::time::interval(
+        (config.retry_interval / RETRY_CHECKS_PER_INTERVAL).max(Duration::from_millis(1)),
+    );
+    let anti_entropy = !config.anti_entropy_interval.is_zero();
+    let mut anti_entropy_timer =
+        tokio::time::interval(config.anti_entropy_interval.max(Duration::from_millis(1)));
+    loop {
+        tokio::select! {
+            (msg, span) = receive_msg_in_span() => {
+                let msg: Message = msg;
+                span.sync_scope(|| node.handle_msg(msg));
+            }
+            _ = gossip_timer.tick(), if node.mode == Mode::Gossip => node.send_gossip(),
+            _ = retry_timer.tick(), if node.mode == Mode::Flood => node.retransmit(),
+            _ = anti_entropy_timer.tick(), if anti_entropy => node.send_digest(),
+        }
+    }
+}
+
+/// How often the flood retransmission deadlines are checked.
+const RETRY_CHECKS_PER_INTERVAL: u32 = 4;
+
+struct Node {
+    config: NodeConfig,
+    mode: Mode,
+    values: ValueStore,
+    flood: FloodState,
+    gossip: GossipState,
+}
+
+impl Node {
+    fn handle_msg(&mut self, msg: Message) {
+        match msg.body.data {
+            BodyData::Broadcast { ref message } => {
+                let versions = self.insert_values(&msg.src, std::slice::from_ref(message));
+                self.spread(&msg.src, versions);
+                send_msg(&msg.create_response(BodyData::BroadcastOk));
+            }
+            BodyData::Gossip { ref messages } => {
+                let versions = self.insert_values(&msg.src, messages);
+                self.spread(&msg.src, versions);
+                send_msg(&msg.create_response(BodyData::GossipOk));
+            }
+            BodyData::BroadcastOk | BodyData::GossipOk => {
+                if let Some(msg_id) = msg.body.in_reply_to {
+                    self.flood.acked(&msg.src, msg_id);
+                    self.gossip.acked(&msg.src, msg_id);
+                }
+            }
+            BodyData::Sync { ref digest } => {
+                let buckets = self.values.digest().differing(digest);
+                let messages = self.values.in_buckets(&buckets);
+                send_msg(&msg.create_response(BodyData::SyncOk { buckets, messages }));
+            }
+            BodyData::SyncOk {
+                ref buckets,
+                ref messages,
+            } => {
+                let versions = self.insert_values(&msg.src, messages);
+                self.spread(&msg.src, versions);
+                let theirs: HashSet<_> = messages.iter().map(|v| v.to_string()).collect();
+                let missing: Vec<_> = self
+                    .values
+                    .in_buckets(buckets)
+                    .into_iter()
+                    .filter(|v| !theirs.contains(&v.to_string()))
+                    .collect();
+                if !missing.is_empty() {
+                    log::info!("Sending {} values missing on {}", missing.len(), msg.src);
+                    self.send_to(
+                        &msg.src,
+                        gen_next_msg_id(),
+                        BodyData::Gossip { messages: missing },
+                    );
+                }
+            }
+            BodyData::Read => {
+                send_msg(&msg.create_response(BodyData::ReadOk {
+                    messages: self.values.snapshot(),
+                }));
+            }
+            _ => log::warn!("Ignoring unexpected message {:?}", msg),
+        }
+    }
+
+    /// Adds values sent by another node, which doesn't need them back.
+    /// Returns the versions of the new ones.
+    fn insert_values(&mut self, src: &str, values: &[BroadcastValue]) -> Vec<Version> {
+        let mut new = Vec::new();
+        let mut known = Vec::new();
+        for value in values {
+            match self.values.insert(value.clone()) {
+                Some(version) => {
+                    new.push(version);
+                    known.push(version);
+                }
+                None => known.extend(self.values.version_of(value)),
+            }
+        }
+        self.flood.received(src, &known);
+        self.gossip.received(src, known);
+        new
+    }
+
+    /// Sends new values right away to every neighbour but the one they came from,
+    /// the gossip mode sends them with the next batch instead.
+    fn spread(&mut self, src: &str, versions: Vec<Version>) {
+        if self.mode != Mode::Flood || versions.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let dests: Vec<_> = self
+            .config
+            .neighbours
+            .iter()
+            .filter(|&node_id| node_id != src)
+            .cloned()
+            .collect();
+        for dest in dests {
+            let msg_id = gen_next_msg_id();
+            let data = match versions[..] {
+                [version] => BodyData::Broadcast {
+                    message: self.values.get(version).clone(),
+                },
+                _ => BodyData::Gossip {
+                    messages: versions.iter().map(|&v| self.values.get(v).clone()).collect(),
+                },
+            };
+            self.send_to(&dest, msg_id, data);
+            self.flood.sent(&dest, msg_id, versions.clone(), now);
+        }
+    }
+
+    fn retransmit(&mut self) {
+        let batches = self
+            .flood
+            .retransmissions(&self.values, Instant::now(), gen_next_msg_id);
+        for (dest, msg_id, messages) in batches {
+            log::info!("Resending {} unacknowledged values to {dest}", messages.len());
+            self.send_to(&dest, msg_id, BodyData::Gossip { messages });
+        }
+    }
+
+    fn send_gossip(&mut self) {
+        for (dest, msg_id, messages) in self.gossip.next_batches(&self.values, gen_next_msg_id) {
+            self.send_to(&dest, msg_id, BodyData::Gossip { messages });
+        }
+    }
+
+    fn send_digest(&self) {
+        if let Some(dest) = self.config.neighbours.choose(&mut rand::thread_rng()) {
+            let digest = self.values.digest().buckets().to_vec();
+            self.send_to(dest, gen_next_msg_id(), BodyData::Sync { digest });
+        }
+    }
+
+    fn send_to(&self, dest: &NodeId, msg_id: MessageId, data: BodyData) {
+        send_msg(&Message {
+            src: self.config.node_id.clone(),
+            dest: dest.clone(),
+            body: Body {
+                msg_id: Some(msg_id),
+                in_reply_to: None,
+                data,
+            },
+        });
+    }
+}
+
+async fn init_node(topology: Topology) -> NodeConfig {
+    let init_msg: Message = receive_msg().await;
+    let (node_id, node_ids) = if let BodyData::Init(ref data) = init_msg.body.data {
+        logging::set_node_id(&data.node_id);
+        send_msg(&init_msg.create_response(BodyData::InitOk));
+        (data.node_id.clone(), data.node_ids.clone())
+    } else {
+        panic!("Expected init msg, got {:?}", init_msg);
+    };
+    let topology_msg: Message = receive_msg().await;
+    if let BodyData::Topology {
+        topology: ref given,
+    } = topology_msg.body.data
+    {
+        send_msg(&topology_msg.create_response(BodyData::TopologyOk));
+        let neighbours = topology.neighbours(&node_id, &node_ids, given);
+        log::info!("Neighbours in {topology:?} topology: {neighbours:?}");
+        NodeConfig {
+            neighbours,
+            node_id,
+        }
+    } else {
+        panic!("Expected topology msg, got {:?}", topology_msg);
+    }
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::anti_entropy::{self, Digest};
+use crate::protocol::broadcast::BroadcastValue;
+
+/// Version of the store, the number of values it holds.
+pub type Version = usize;
+
+/// Broadcast values in arrival order, a value is added at the current version.
+///
+/// Values are compared by their compact JSON encoding, object keys are sorted
+/// by `serde_json`, so equal values always have the same encoding.
+#[derive(Default)]
+pub struct ValueStore {
+    values: Arc<Vec<BroadcastValue>>,
+    versions: HashMap<String, Version>,
+    digest: Digest,
+}
+
+impl ValueStore {
+    /// Returns the version of the value if it's new.
+    pub fn insert(&mut self, value: BroadcastValue) -> Option<Version> {
+        let key = value.to_string();
+        if self.versions.contains_key(&key) {
+            return None;
+        }
+        let version = self.values.len();
+        self.digest.insert(&key);
+        self.versions.insert(key, version);
+        // Only copies if a read response still holds the previous snapshot.
+        Arc::make_mut(&mut self.values).push(value);
+        Some(version)
+    }
+
+    pub fn version(&self) -> Version {
+        self.values.len()
+    }
+
+    pub fn version_of(&self, value: &BroadcastValue) -> Option<Version> {
+        self.versions.get(&value.to_string()).copied()
+    }
+
+    pub fn get(&self, version: Version) -> &BroadcastValue {
+        &self.values[version]
+    }
+
+    pub fn snapshot(&self) -> Arc<Vec<BroadcastValue>> {
+        self.values.clone()
+    }
+
+    pub fn digest(&self) -> &Digest {
+        &self.digest
+    }
+
+    pub fn in_buckets(&self, buckets: &[usize]) -> Vec<BroadcastValue> {
+        if buckets.is_empty() {
+            return Vec::new();
+        }
+        self.versions
+            .iter()
+            .filter(|(key, _)| anti_entropy::in_buckets(*key, buckets))
+            .map(|(_, &version)| self.values[version].clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod store_tests {
+    use serde_json::json;
+
+    use super::ValueStore;
+
+    #[test]
+    fn dedups_json_values() {
+        let mut store = ValueStore::default();
+        assert_eq!(store.insert(json!({"a": 1, "b": [2]})), Some(0));
+        assert_eq!(store.insert(json!(3)), Some(1));
+        assert_eq!(store.insert(json!({"b": [2], "a": 1})), None);
+        assert_eq!(store.version_of(&json!(3)), Some(1));
+        assert_eq!(store.version(), 2);
+    }
+
+    #[test]
+    fn snapshot_is_not_affected_by_inserts() {
+        let mut store = ValueStore::default();
+        store.insert(json!(1));
+        let snapshot = store.snapshot();
+        store.insert(json!(2));
+        assert_eq!(*snapshot, vec![json!(1)]);
+        assert_eq!(*store.snapshot(), vec![json!(1), json!(2)]);
+    }
+}
@@ -0,0 +1,153 @@
+use std::{collections::HashMap, str::FromStr};
+
+use crate::protocol::NodeId;
+
+/// How the neighbours of a node are chosen. All but `Given` are computed from
+/// the node ids of the init message, every node derives the same graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// The one from the Maelstrom `topology` message.
+    Given,
+    /// The first node is connected to every other one.
+    Star,
+    /// Tree with the given number of children per node.
+    Tree(usize),
+    /// Ring where every node also links to the nodes 2, 4, 8... positions away.
+    Ring,
+    /// Every node is connected to every other one.
+    Mesh,
+}
+
+impl Topology {
+    pub fn parse(name: &str, tree_arity: usize) -> Result<Self, String> {
+        match name {
+            "tree" if tree_arity == 0 => Err("tree arity must be positive".to_owned()),
+            "tree" => Ok(Topology::Tree(tree_arity)),
+            other => other.parse(),
+        }
+    }
+
+    pub fn neighbours(
+        &self,
+        node_id: &NodeId,
+        node_ids: &[NodeId],
+        given: &HashMap<NodeId, Vec<NodeId>>,
+    ) -> Vec<NodeId> {
+        let Some(pos) = node_ids.iter().position(|id| id == node_id) else {
+            return given.get(node_id).cloned().unwrap_or_default();
+        };
+        let n = node_ids.len();
+        let mut indices: Vec<usize> = match *self {
+            Topology::Given => return given.get(node_id).cloned().unwrap_or_default(),
+            Topology::Star if pos == 0 => (1..n).collect(),
+            Topology::Star => vec![0],
+            Topology::Tree(arity) => {
+                let parent = (pos > 0).then(|| (pos - 1) / arity);
+                let children = (pos * arity + 1..=pos * arity + arity).filter(|&i| i < n);
+                parent.into_iter().chain(children).collect()
+            }
+            Topology::Ring => std::iter::successors(Some(1), |d| Some(d * 2))
+                .take_while(|&d| d < n)
+                .flat_map(|d| [(pos + d) % n, (pos + n - d) % n])
+                .collect(),
+            Topology::Mesh => (0..n).collect(),
+        };
+        indices.sort();
+        indices.dedup();
+        indices
+            .into_iter()
+            .filter(|&i| i != pos)
+            .map(|i| node_ids[i].clone())
+            .collect()
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "given" => Ok(Topology::Given),
+            "star" => Ok(Topology::Star),
+            "ring" => Ok(Topology::Ring),
+            "mesh" => Ok(Topology::Mesh),
+            other => Err(format!(
+                "expected given, star, tree, ring or mesh, got {other}"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod topology_tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::Topology;
+    use crate::protocol::NodeId;
+
+    fn node_ids(n: usize) -> Vec<NodeId> {
+        (0..n).map(|i| format!("n{i}")).collect()
+    }
+
+    fn graph(topology: Topology, n: usize) -> HashMap<NodeId, Vec<NodeId>> {
+        let ids = node_ids(n);
+        ids.iter()
+            .map(|id| (id.clone(), topology.neighbours(id, &ids, &HashMap::new())))
+            .collect()
+    }
+
+    #[test]
+    fn symmetric_and_connected() {
+        for topology in [
+            Topology::Star,
+            Topology::Tree(1),
+            Topology::Tree(3),
+            Topology::Ring,
+            Topology::Mesh,
+        ] {
+            for n in 1..40 {
+                let graph = graph(topology, n);
+                for (node, neighbours) in &graph {
+                    assert!(!neighbours.contains(node));
+                    for neighbour in neighbours {
+                        assert!(graph[neighbour].contains(node), "{topology:?} {n}");
+                    }
+                }
+                let mut reached = HashSet::from(["n0".to_owned()]);
+                let mut queue = vec!["n0".to_owned()];
+                while let Some(node) = queue.pop() {
+                    for neighbour in &graph[&node] {
+                        if reached.insert(neighbour.clone()) {
+                            queue.push(neighbour.clone());
+                        }
+                    }
+                }
+                assert_eq!(reached.len(), n, "{topology:?} {n}");
+            }
+        }
+    }
+
+    #[test]
+    fn degrees() {
+        let tree = graph(Topology::Tree(4), 25);
+        assert_eq!(tree["n0"].len(), 4);
+        assert_eq!(tree["n1"].len(), 5);
+        assert_eq!(tree["n24"].len(), 1);
+
+        let star = graph(Topology::Star, 25);
+        assert_eq!(star["n0"].len(), 24);
+        assert_eq!(star["n7"], vec!["n0".to_owned()]);
+
+        assert_eq!(graph(Topology::Ring, 25)["n0"].len(), 10);
+    }
+
+    #[test]
+    fn given() {
+        let ids = node_ids(3);
+        let given = HashMap::from([("n1".to_owned(), vec!["n2".to_owned()])]);
+        assert_eq!(
+            Topology::Given.neighbours(&ids[1], &ids, &given),
+            vec!["n2".to_owned()]
+        );
+    }
+}
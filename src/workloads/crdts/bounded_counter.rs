@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::pn_counter::CounterState;
+use super::{Config, Crdt, OPTIONS};
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::protocol::{crdts::counter::*, ErrorCode, ErrorData, NodeId};
+use crate::workloads::Workload;
+
+pub struct BoundedCounterWorkload;
+
+impl Workload for BoundedCounterWorkload {
+    fn name(&self) -> &'static str {
+        "bounded-counter"
+    }
+
+    fn description(&self) -> &'static str {
+        "Counter CRDT that never goes below zero, nodes move the rights to decrement between them"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        log::info!("Running bounded counter workload");
+        super::run(BoundedCounter::default(), Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+type CounterValue = i64;
+
+/// Every increment gives its node the right to decrement by as much, and a
+/// node only decrements with its own rights. The rights of all nodes add up
+/// to the value, so it stays non-negative without coordinating decrements.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub(super) struct BoundedState {
+    counts: BTreeMap<NodeId, CounterState>,
+    /// Rights moved between nodes, by giver and receiver.
+    transfers: BTreeMap<NodeId, BTreeMap<NodeId, u64>>,
+    /// Rights every node asked the others for so far.
+    wanted: BTreeMap<NodeId, u64>,
+}
+
+impl BoundedState {
+    fn value(&self) -> CounterValue {
+        self.counts
+            .values()
+            .map(|state| state.pos as i64 - state.neg as i64)
+            .sum()
+    }
+
+    fn received(&self, node_id: &NodeId) -> u64 {
+        self.transfers
+            .values()
+            .filter_map(|to| to.get(node_id))
+            .sum()
+    }
+
+    fn rights(&self, node_id: &NodeId) -> u64 {
+        let own = self.counts.get(node_id).cloned().unwrap_or_default();
+        let given: u64 = self
+            .transfers
+            .get(node_id)
+            .into_iter()
+            .flat_map(|to| to.values())
+            .sum();
+        (own.pos + self.received(node_id)).saturating_sub(own.neg + given)
+    }
+
+    /// Everything is grow-only, so the merge takes the maximum of each entry.
+    fn merge(&mut self, other: &BoundedState) {
+        for (node_id, theirs) in &other.counts {
+            let ours = self.counts.entry(node_id.clone()).or_default();
+            ours.pos = ours.pos.max(theirs.pos);
+            ours.neg = ours.neg.max(theirs.neg);
+        }
+        for (giver, to) in &other.transfers {
+            let ours = self.transfers.entry(giver.clone()).or_default();
+            for (receiver, &amount) in to {
+                let amount_ours = ours.entry(receiver.clone()).or_default();
+                *amount_ours = (*amount_ours).max(amount);
+            }
+        }
+        for (node_id, &amount) in &other.wanted {
+            let ours = self.wanted.entry(node_id.clone()).or_default();
+            *ours = (*ours).max(amount);
+        }
+    }
+
+    /// Entries only the node itself changes.
+    fn own(&self, node_id: &NodeId) -> BoundedState {
+        BoundedState {
+            counts: entry_of(&self.counts, node_id),
+            transfers: entry_of(&self.transfers, node_id),
+            wanted: entry_of(&self.wanted, node_id),
+        }
+    }
+}
+
+fn entry_of<V: Clone>(map: &BTreeMap<NodeId, V>, node_id: &NodeId) -> BTreeMap<NodeId, V> {
+    map.get_key_value(node_id)
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .into_iter()
+        .collect()
+}
+
+#[derive(Default)]
+pub(super) struct BoundedCounter {
+    node_id: NodeId,
+    state: BoundedState,
+    /// The own entries changed since the last delta.
+    changed: bool,
+}
+
+impl BoundedCounter {
+    fn add(&mut self, delta: CounterValue) -> CounterBodyData<CounterValue> {
+        let counts = self.state.counts.entry(self.node_id.clone()).or_default();
+        if delta >= 0 {
+            counts.pos += delta.unsigned_abs();
+            self.changed = true;
+            return CounterBodyData::AddOk;
+        }
+        let amount = delta.unsigned_abs();
+        let rights = self.state.rights(&self.node_id);
+        if amount <= rights {
+            self.state.counts.get_mut(&self.node_id).unwrap().neg += amount;
+            self.changed = true;
+            return CounterBodyData::AddOk;
+        }
+        let value = self.state.value();
+        if value < amount as i64 {
+            return CounterBodyData::Error(ErrorData::new(
+                format!("Counter at {value} can't go below zero"),
+                ErrorCode::PreconditionFailed,
+            ));
+        }
+        // Other nodes hold the missing rights, they give them on the next merge.
+        let wanted = self.state.received(&self.node_id) + (amount - rights);
+        let ours = self.state.wanted.entry(self.node_id.clone()).or_default();
+        if wanted > *ours {
+            *ours = wanted;
+            self.changed = true;
+        }
+        CounterBodyData::Error(ErrorData::new(
+            format!("Only {rights} of {amount} rights here, asked other nodes for the rest"),
+            ErrorCode::TemporarilyUnavailable,
+        ))
+    }
+
+    /// Gives the nodes asking for rights as much of them as this node has.
+    fn grant(&mut self) {
+        let asking: Vec<_> = self
+            .state
+            .wanted
+            .iter()
+            .filter(|(node_id, _)| **node_id != self.node_id)
+            .map(|(node_id, &wanted)| {
+                (
+                    node_id.clone(),
+                    wanted.saturating_sub(self.state.received(node_id)),
+                )
+            })
+            .filter(|(_, missing)| *missing > 0)
+            .collect();
+        for (node_id, missing) in asking {
+            let amount = missing.min(self.state.rights(&self.node_id));
+            if amount == 0 {
+                return;
+            }
+            log::info!("Giving {amount} rights to {node_id}");
+            *self
+                .state
+                .transfers
+                .entry(self.node_id.clone())
+                .or_default()
+                .entry(node_id)
+                .or_default() += amount;
+            self.changed = true;
+        }
+    }
+}
+
+impl Crdt for BoundedCounter {
+    type Body = CounterBodyData<CounterValue>;
+    type State = BoundedState;
+
+    const DELTAS: bool = true;
+
+    fn init(&mut self, node_id: &NodeId) {
+        self.node_id = node_id.clone();
+    }
+
+    fn handle_msg(&mut self, body: &Self::Body) -> Option<Self::Body> {
+        match body {
+            CounterBodyData::Add { delta } => Some(self.add(*delta)),
+            CounterBodyData::Read => Some(CounterBodyData::ReadOk {
+                value: self.state.value(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, state: &Self::State) {
+        self.state.merge(state);
+        self.grant();
+    }
+
+    fn get_state(&self) -> Self::State {
+        self.state.clone()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        std::mem::take(&mut self.changed).then(|| self.state.own(&self.node_id))
+    }
+}
+
+#[cfg(test)]
+mod bounded_counter_tests {
+    use rand::{rngs::StdRng, Rng};
+
+    use super::{BoundedCounter, BoundedState, CounterValue};
+    use crate::protocol::{crdts::counter::*, ErrorCode, NodeId};
+    use crate::workloads::crdts::laws::{check_laws, TestCrdt};
+    use crate::workloads::crdts::Crdt;
+
+    impl TestCrdt for BoundedCounter {
+        type Observed = BoundedState;
+
+        fn replica(node_id: &NodeId) -> Self {
+            node(node_id)
+        }
+
+        fn random_op(rng: &mut StdRng) -> CounterBodyData<CounterValue> {
+            CounterBodyData::Add {
+                delta: rng.gen_range(-6..5),
+            }
+        }
+
+        fn observe(&mut self) -> Self::Observed {
+            self.get_state()
+        }
+    }
+
+    fn node(node_id: &str) -> BoundedCounter {
+        let mut counter = BoundedCounter::default();
+        counter.init(&node_id.to_owned());
+        counter
+    }
+
+    fn add(counter: &mut BoundedCounter, delta: CounterValue) -> Result<(), ErrorCode> {
+        match counter.handle_msg(&CounterBodyData::Add { delta }) {
+            Some(CounterBodyData::AddOk) => Ok(()),
+            Some(CounterBodyData::Error(err)) => Err(err.code),
+            other => panic!("Unexpected response {other:?}"),
+        }
+    }
+
+    #[test]
+    fn moves_rights_on_request() {
+        let (mut n1, mut n2) = (node("n1"), node("n2"));
+        add(&mut n1, 5).unwrap();
+        n2.update(&n1.get_state());
+        assert_eq!(add(&mut n2, -6), Err(ErrorCode::PreconditionFailed));
+        assert_eq!(add(&mut n2, -3), Err(ErrorCode::TemporarilyUnavailable));
+
+        n1.update(&n2.take_delta().unwrap());
+        let delta = n1.take_delta().unwrap();
+        n2.merge_delta(&delta);
+        assert_eq!(add(&mut n2, -3), Ok(()));
+        assert_eq!(add(&mut n1, -3), Err(ErrorCode::TemporarilyUnavailable));
+        assert_eq!(add(&mut n1, -2), Ok(()));
+        n1.update(&n2.get_state());
+        assert_eq!(n1.state.value(), 0);
+        assert_eq!(add(&mut n1, -1), Err(ErrorCode::PreconditionFailed));
+    }
+
+    #[test]
+    fn merge_laws() {
+        check_laws::<BoundedCounter>();
+    }
+}
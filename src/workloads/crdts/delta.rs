@@ -0,0 +1,119 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::protocol::{crdts::ReplicationVersion, NodeId};
+
+/// Deltas of the local changes and the version every peer has acknowledged,
+/// a peer gets the deltas above its version or the full state once some of
+/// them are dropped.
+pub struct DeltaLog<S> {
+    version: ReplicationVersion,
+    deltas: VecDeque<(ReplicationVersion, S)>,
+    max_deltas: usize,
+    acked: HashMap<NodeId, ReplicationVersion>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Replication<'a, S> {
+    UpToDate,
+    Deltas {
+        version: ReplicationVersion,
+        deltas: Vec<&'a S>,
+    },
+    FullState {
+        version: ReplicationVersion,
+    },
+}
+
+impl<S> DeltaLog<S> {
+    pub fn new(peers: &[NodeId], max_deltas: usize) -> Self {
+        Self {
+            version: 0,
+            deltas: VecDeque::new(),
+            max_deltas,
+            acked: peers.iter().map(|peer| (peer.clone(), 0)).collect(),
+        }
+    }
+
+    pub fn push(&mut self, delta: S) {
+        self.version += 1;
+        self.deltas.push_back((self.version, delta));
+        if self.deltas.len() > self.max_deltas {
+            self.deltas.pop_front();
+        }
+    }
+
+    pub fn acked(&mut self, peer: &str, version: ReplicationVersion) {
+        if let Some(acked) = self.acked.get_mut(peer) {
+            *acked = (*acked).max(version);
+        }
+    }
+
+    pub fn next_for(&self, peer: &str) -> Replication<'_, S> {
+        let acked = self.acked.get(peer).copied().unwrap_or_default();
+        if acked >= self.version {
+            return Replication::UpToDate;
+        }
+        match self.deltas.front() {
+            Some(&(oldest, _)) if oldest <= acked + 1 => Replication::Deltas {
+                version: self.version,
+                deltas: self
+                    .deltas
+                    .iter()
+                    .filter(|(version, _)| *version > acked)
+                    .map(|(_, delta)| delta)
+                    .collect(),
+            },
+            _ => Replication::FullState {
+                version: self.version,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod delta_tests {
+    use super::{DeltaLog, Replication};
+
+    #[test]
+    fn sends_deltas_until_acked() {
+        let mut log = DeltaLog::new(&["n2".to_owned(), "n3".to_owned()], 10);
+        assert_eq!(log.next_for("n2"), Replication::UpToDate);
+        log.push(1);
+        log.push(2);
+        log.acked("n2", 1);
+        assert_eq!(
+            log.next_for("n2"),
+            Replication::Deltas {
+                version: 2,
+                deltas: vec![&2]
+            }
+        );
+        assert_eq!(
+            log.next_for("n3"),
+            Replication::Deltas {
+                version: 2,
+                deltas: vec![&1, &2]
+            }
+        );
+        log.acked("n2", 2);
+        log.acked("n2", 1);
+        assert_eq!(log.next_for("n2"), Replication::UpToDate);
+    }
+
+    #[test]
+    fn falls_back_to_full_state() {
+        let mut log = DeltaLog::new(&["n2".to_owned(), "n3".to_owned()], 2);
+        for delta in 1..=3 {
+            log.push(delta);
+        }
+        log.acked("n2", 1);
+        assert_eq!(
+            log.next_for("n2"),
+            Replication::Deltas {
+                version: 3,
+                deltas: vec![&2, &3]
+            }
+        );
+        assert_eq!(log.next_for("n3"), Replication::FullState { version: 3 });
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::NodeId;
+
+/// Unique id of an update, the node that made it and its counter on that node.
+pub type Dot = (NodeId, u64);
+
+/// Set of dots seen by a replica, the contiguous ones from every node are kept
+/// as a version vector and only the rest are listed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DotContext {
+    versions: BTreeMap<NodeId, u64>,
+    dots: BTreeSet<Dot>,
+}
+
+impl DotContext {
+    pub fn from_dots(dots: impl IntoIterator<Item = Dot>) -> Self {
+        let mut context = Self {
+            versions: BTreeMap::new(),
+            dots: dots.into_iter().collect(),
+        };
+        context.compact();
+        context
+    }
+
+    pub fn contains(&self, (node_id, counter): &Dot) -> bool {
+        self.versions.get(node_id).is_some_and(|v| counter <= v)
+            || self.dots.contains(&(node_id.clone(), *counter))
+    }
+
+    /// Dots of a node are only made by the node itself, so its own dots are
+    /// always contiguous.
+    pub fn next_dot(&mut self, node_id: &NodeId) -> Dot {
+        let version = self.versions.entry(node_id.clone()).or_default();
+        *version += 1;
+        (node_id.clone(), *version)
+    }
+
+    pub fn merge(&mut self, other: &DotContext) {
+        for (node_id, &version) in &other.versions {
+            let ours = self.versions.entry(node_id.clone()).or_default();
+            *ours = (*ours).max(version);
+        }
+        self.dots.extend(other.dots.iter().cloned());
+        self.compact();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty() && self.dots.is_empty()
+    }
+
+    /// Dots are visited in counter order per node, so a dot right above the
+    /// version extends it and the following ones are checked against that.
+    fn compact(&mut self) {
+        for (node_id, counter) in std::mem::take(&mut self.dots) {
+            let version = self.versions.entry(node_id.clone()).or_default();
+            if counter == *version + 1 {
+                *version = counter;
+            } else if counter > *version {
+                self.dots.insert((node_id, counter));
+            }
+        }
+        self.versions.retain(|_, version| *version > 0);
+    }
+}
+
+#[cfg(test)]
+mod dots_tests {
+    use super::DotContext;
+
+    fn dot(node_id: &str, counter: u64) -> (String, u64) {
+        (node_id.to_owned(), counter)
+    }
+
+    #[test]
+    fn compacts_contiguous_dots() {
+        let mut context = DotContext::from_dots([dot("n1", 1), dot("n1", 3), dot("n2", 2)]);
+        assert!(context.contains(&dot("n1", 1)));
+        assert!(!context.contains(&dot("n1", 2)));
+        context.merge(&DotContext::from_dots([dot("n1", 2), dot("n2", 1)]));
+        assert_eq!(
+            context,
+            DotContext::from_dots([
+                dot("n1", 1),
+                dot("n1", 2),
+                dot("n1", 3),
+                dot("n2", 1),
+                dot("n2", 2)
+            ])
+        );
+        assert!(context.dots.is_empty());
+        assert_eq!(context.next_dot(&"n1".to_owned()), dot("n1", 4));
+    }
+}
@@ -1,27 +1,63 @@
 use std::collections::HashMap;
 
-use super::Crdt;
+use super::{Config, Crdt, OPTIONS};
+use crate::cli::{CliError, OptionSpec, Options};
 use crate::protocol::{crdts::counter::*, NodeId};
+use crate::workloads::Workload;
 
-pub fn run() {
-    eprintln!("Running G-Counter workload");
-    super::run(GCounter {
-        node_id: None,
-        values: HashMap::new(),
-    });
+pub struct GCounterWorkload;
+
+impl Workload for GCounterWorkload {
+    fn name(&self) -> &'static str {
+        "g-counter"
+    }
+
+    fn description(&self) -> &'static str {
+        "Grow-only counter CRDT"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("g-counter")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
+    log::info!("Running G-Counter workload");
+    super::run(
+        GCounter {
+            node_id: None,
+            values: HashMap::new(),
+            changed: false,
+        },
+        config,
+    );
 }
 
 type CounterValue = u64;
 
-struct GCounter {
+#[derive(Default)]
+pub(super) struct GCounter {
     node_id: Option<String>,
     values: HashMap<NodeId, CounterValue>,
+    /// The own entry changed since the last delta.
+    changed: bool,
 }
 
 impl Crdt for GCounter {
     type Body = CounterBodyData<CounterValue>;
     type State = HashMap<String, CounterValue>;
 
+    const DELTAS: bool = true;
+
     fn init(&mut self, node_id: &NodeId) {
         self.node_id = Some(node_id.clone());
         self.values.insert(node_id.clone(), 0);
@@ -31,6 +67,7 @@ impl Crdt for GCounter {
         match body {
             CounterBodyData::Add { delta } => {
                 *self.values.get_mut(self.node_id.as_ref().unwrap()).unwrap() += delta;
+                self.changed = true;
                 Some(CounterBodyData::AddOk)
             }
             CounterBodyData::Read => Some(CounterBodyData::ReadOk {
@@ -52,4 +89,50 @@ impl Crdt for GCounter {
     fn get_state(&self) -> Self::State {
         self.values.iter().map(|(k, &v)| (k.clone(), v)).collect()
     }
-}
\ No newline at end of file
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        if !std::mem::take(&mut self.changed) {
+            return None;
+        }
+        let node_id = self.node_id.as_ref().unwrap();
+        Some(HashMap::from([(node_id.clone(), self.values[node_id])]))
+    }
+}
+
+#[cfg(test)]
+mod g_counter_tests {
+    use std::collections::BTreeMap;
+
+    use rand::{rngs::StdRng, Rng};
+
+    use super::{CounterValue, GCounter};
+    use crate::protocol::{crdts::counter::*, NodeId};
+    use crate::workloads::crdts::laws::{check_laws, TestCrdt};
+    use crate::workloads::crdts::Crdt;
+
+    impl TestCrdt for GCounter {
+        /// Nodes that haven't added anything may be missing.
+        type Observed = BTreeMap<NodeId, CounterValue>;
+
+        fn replica(node_id: &NodeId) -> Self {
+            let mut counter = GCounter::default();
+            counter.init(node_id);
+            counter
+        }
+
+        fn random_op(rng: &mut StdRng) -> CounterBodyData<CounterValue> {
+            CounterBodyData::Add {
+                delta: rng.gen_range(0..5),
+            }
+        }
+
+        fn observe(&mut self) -> Self::Observed {
+            self.get_state().into_iter().filter(|&(_, v)| v > 0).collect()
+        }
+    }
+
+    #[test]
+    fn merge_laws() {
+        check_laws::<GCounter>();
+    }
+}
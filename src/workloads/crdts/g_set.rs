@@ -1,27 +1,64 @@
 use std::collections::HashSet;
 
-use super::Crdt;
+use super::{Config, Crdt, OPTIONS};
+use crate::cli::{CliError, OptionSpec, Options};
 use crate::protocol::crdts::g_set::*;
+use crate::workloads::Workload;
 
-pub fn run() {
-    eprintln!("Running G-Set workload");
-    super::run(GSet {
-        values: HashSet::new(),
-    });
+pub struct GSetWorkload;
+
+impl Workload for GSetWorkload {
+    fn name(&self) -> &'static str {
+        "g-set"
+    }
+
+    fn description(&self) -> &'static str {
+        "Grow-only set CRDT"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("g-set")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
+    log::info!("Running G-Set workload");
+    super::run(
+        GSet {
+            values: HashSet::new(),
+            added: Vec::new(),
+        },
+        config,
+    );
 }
 
 struct GSet {
     values: HashSet<ElementValue>,
+    /// Elements added since the last delta.
+    added: Vec<ElementValue>,
 }
 
 impl Crdt for GSet {
     type Body = GSetBodyData;
     type State = GsetState;
 
+    const DELTAS: bool = true;
+
     fn handle_msg(&mut self, body: &Self::Body) -> Option<Self::Body> {
         match body {
             GSetBodyData::Add { element } => {
-                self.values.insert(*element);
+                if self.values.insert(*element) {
+                    self.added.push(*element);
+                }
                 Some(GSetBodyData::AddOk)
             }
             GSetBodyData::Read => Some(GSetBodyData::ReadOk {
@@ -35,7 +72,52 @@ impl Crdt for GSet {
         self.values.extend(state.iter());
     }
 
+    /// Sorted, equal sets have equal digests.
     fn get_state(&self) -> Self::State {
-        self.values.iter().cloned().collect()
+        let mut values: Self::State = self.values.iter().cloned().collect();
+        values.sort_unstable();
+        values
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        (!self.added.is_empty()).then(|| std::mem::take(&mut self.added))
+    }
+}
+
+#[cfg(test)]
+mod g_set_tests {
+    use rand::{rngs::StdRng, Rng};
+
+    use super::GSet;
+    use crate::protocol::{crdts::g_set::*, NodeId};
+    use crate::workloads::crdts::laws::{check_laws, TestCrdt};
+    use crate::workloads::crdts::Crdt;
+
+    impl TestCrdt for GSet {
+        type Observed = Vec<ElementValue>;
+
+        fn replica(_node_id: &NodeId) -> Self {
+            GSet {
+                values: Default::default(),
+                added: Vec::new(),
+            }
+        }
+
+        fn random_op(rng: &mut StdRng) -> GSetBodyData {
+            GSetBodyData::Add {
+                element: rng.gen_range(0..20),
+            }
+        }
+
+        fn observe(&mut self) -> Self::Observed {
+            let mut elements = self.get_state();
+            elements.sort();
+            elements
+        }
+    }
+
+    #[test]
+    fn merge_laws() {
+        check_laws::<GSet>();
     }
 }
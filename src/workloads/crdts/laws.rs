@@ -0,0 +1,156 @@
+//! Properties every [`Crdt`] should have, checked on random histories of
+//! replicas exchanging deltas and states.
+
+use std::fmt::Debug;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::Crdt;
+use crate::protocol::NodeId;
+
+const REPLICAS: usize = 3;
+const STEPS: usize = 60;
+const SEEDS: u64 = 10;
+
+pub trait TestCrdt: Crdt + Sized {
+    /// What replicas that have seen the same updates agree on, like the read
+    /// value or the state without entries that don't change it.
+    type Observed: PartialEq + Debug;
+
+    fn replica(node_id: &NodeId) -> Self;
+    fn random_op(rng: &mut StdRng) -> Self::Body;
+    fn observe(&mut self) -> Self::Observed;
+}
+
+enum Payload<S> {
+    Delta(S),
+    State(S),
+}
+
+/// Replicas make random updates, their deltas go to every other replica and
+/// now and then a full state to a random one. Messages are delivered in random
+/// order and some of them more than once. Returns the replicas once every
+/// message is delivered and the states they went through.
+fn simulate<C: TestCrdt>(seed: u64) -> (Vec<C>, Vec<C::State>) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let nodes: Vec<NodeId> = (0..REPLICAS).map(|i| format!("n{i}")).collect();
+    let mut replicas: Vec<C> = nodes.iter().map(C::replica).collect();
+    let mut in_flight: Vec<(usize, Payload<C::State>)> = Vec::new();
+    let mut states = Vec::new();
+    for _ in 0..STEPS {
+        let i = rng.gen_range(0..REPLICAS);
+        match rng.gen_range(0..10) {
+            0..=4 => {
+                replicas[i].handle_msg(&C::random_op(&mut rng));
+                if let Some(delta) = replicas[i].take_delta() {
+                    for j in (0..REPLICAS).filter(|&j| j != i) {
+                        in_flight.push((j, Payload::Delta(delta.clone())));
+                    }
+                }
+            }
+            5 => {
+                let j = (i + rng.gen_range(1..REPLICAS)) % REPLICAS;
+                in_flight.push((j, Payload::State(replicas[i].get_state())));
+            }
+            _ => deliver(&mut rng, &mut replicas, &mut in_flight),
+        }
+        states.push(replicas[i].get_state());
+    }
+    while !in_flight.is_empty() {
+        deliver(&mut rng, &mut replicas, &mut in_flight);
+    }
+    if !C::DELTAS {
+        for i in 0..REPLICAS {
+            let state = replicas[i].get_state();
+            replicas
+                .iter_mut()
+                .for_each(|replica| replica.update(&state));
+        }
+    }
+    (replicas, states)
+}
+
+fn deliver<C: Crdt>(
+    rng: &mut StdRng,
+    replicas: &mut [C],
+    in_flight: &mut Vec<(usize, Payload<C::State>)>,
+) {
+    if in_flight.is_empty() {
+        return;
+    }
+    let idx = rng.gen_range(0..in_flight.len());
+    let duplicate = rng.gen_range(0..4) == 0;
+    let (dest, payload) = if duplicate {
+        let (dest, ref payload) = in_flight[idx];
+        let payload = match payload {
+            Payload::Delta(s) => Payload::Delta(s.clone()),
+            Payload::State(s) => Payload::State(s.clone()),
+        };
+        (dest, payload)
+    } else {
+        in_flight.swap_remove(idx)
+    };
+    match payload {
+        Payload::Delta(delta) => replicas[dest].merge_delta(&delta),
+        Payload::State(state) => replicas[dest].update(&state),
+    }
+    // Changes made in response to the merge, like the CRDT node sends them.
+    if let Some(delta) = replicas[dest].take_delta() {
+        for j in (0..replicas.len()).filter(|&j| j != dest) {
+            in_flight.push((j, Payload::Delta(delta.clone())));
+        }
+    }
+}
+
+/// A new replica that merged the states in order.
+fn merged<C: TestCrdt>(states: &[&C::State]) -> C {
+    let mut replica = C::replica(&"probe".to_owned());
+    for state in states {
+        replica.update(state);
+    }
+    replica
+}
+
+fn observed<C: TestCrdt>(states: &[&C::State]) -> C::Observed {
+    merged::<C>(states).observe()
+}
+
+/// Replicas converge once every message is delivered, and `update` is
+/// idempotent, commutative and associative on the states they went through.
+pub fn check_laws<C: TestCrdt>() {
+    for seed in 0..SEEDS {
+        let (mut replicas, states) = simulate::<C>(seed);
+        let expected = replicas[0].observe();
+        for replica in &mut replicas[1..] {
+            assert_eq!(
+                replica.observe(),
+                expected,
+                "replicas diverged, seed {seed}"
+            );
+        }
+
+        for a in states.iter().step_by(7) {
+            assert_eq!(
+                observed::<C>(&[a, a]),
+                observed::<C>(&[a]),
+                "not idempotent, seed {seed}"
+            );
+            for b in states.iter().step_by(5) {
+                assert_eq!(
+                    observed::<C>(&[a, b]),
+                    observed::<C>(&[b, a]),
+                    "not commutative, seed {seed}"
+                );
+                let ab = merged::<C>(&[a, b]).get_state();
+                for c in states.iter().step_by(11) {
+                    let bc = merged::<C>(&[b, c]).get_state();
+                    assert_eq!(
+                        observed::<C>(&[&ab, c]),
+                        observed::<C>(&[a, &bc]),
+                        "not associative, seed {seed}"
+                    );
+                }
+            }
+        }
+    }
+}
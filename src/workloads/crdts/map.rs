@@ -0,0 +1,480 @@
+use std::collections::{btree_map, BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::g_counter::GCounter;
+use super::or_set::{OrSet, OrSetState};
+use super::pn_counter::PnCounter;
+use super::registers::LwwRegister;
+use super::{Config, Crdt, OPTIONS};
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::protocol::crdts::{
+    counter::CounterBodyData, map::*, or_set::OrSetBodyData, register::RegisterBodyData,
+};
+use crate::protocol::{ErrorCode, ErrorData, NodeId};
+use crate::workloads::Workload;
+
+pub struct CrdtMapWorkload;
+
+impl Workload for CrdtMapWorkload {
+    fn name(&self) -> &'static str {
+        "crdt-map"
+    }
+
+    fn description(&self) -> &'static str {
+        "Map of keys to G-Counter, PN-Counter, OR-Set or LWW-Register CRDTs"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        log::info!("Running CRDT map workload");
+        super::run(CrdtMap::default(), Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+/// Kinds of CRDTs a key can hold. When nodes create a key with different
+/// kinds, the one declared last wins everywhere and the other is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    GCounter,
+    PnCounter,
+    OrSet,
+    LwwRegister,
+}
+
+impl Kind {
+    fn of_body(body: &NestedBody) -> Kind {
+        match body {
+            NestedBody::GCounter(_) => Kind::GCounter,
+            NestedBody::PnCounter(_) => Kind::PnCounter,
+            NestedBody::OrSet(_) => Kind::OrSet,
+            NestedBody::LwwRegister(_) => Kind::LwwRegister,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "crdt", content = "state", rename_all = "kebab-case")]
+enum EntryState {
+    GCounter(<GCounter as Crdt>::State),
+    PnCounter(<PnCounter as Crdt>::State),
+    OrSet(OrSetState),
+    LwwRegister(<LwwRegister as Crdt>::State),
+}
+
+impl EntryState {
+    fn kind(&self) -> Kind {
+        match self {
+            EntryState::GCounter(_) => Kind::GCounter,
+            EntryState::PnCounter(_) => Kind::PnCounter,
+            EntryState::OrSet(_) => Kind::OrSet,
+            EntryState::LwwRegister(_) => Kind::LwwRegister,
+        }
+    }
+}
+
+type MapState = BTreeMap<MapKey, EntryState>;
+
+/// The CRDT under a key, everything is passed on to it.
+enum Entry {
+    GCounter(GCounter),
+    PnCounter(PnCounter),
+    OrSet(OrSet),
+    LwwRegister(LwwRegister),
+}
+
+impl Entry {
+    fn new(kind: Kind, node_id: &NodeId) -> Entry {
+        match kind {
+            Kind::GCounter => Entry::GCounter(initialized(node_id)),
+            Kind::PnCounter => Entry::PnCounter(initialized(node_id)),
+            Kind::OrSet => Entry::OrSet(initialized(node_id)),
+            Kind::LwwRegister => Entry::LwwRegister(initialized(node_id)),
+        }
+    }
+
+    fn kind(&self) -> Kind {
+        match self {
+            Entry::GCounter(_) => Kind::GCounter,
+            Entry::PnCounter(_) => Kind::PnCounter,
+            Entry::OrSet(_) => Kind::OrSet,
+            Entry::LwwRegister(_) => Kind::LwwRegister,
+        }
+    }
+
+    fn handle_msg(&mut self, body: &NestedBody) -> Option<NestedBody> {
+        match (self, body) {
+            (Entry::GCounter(c), NestedBody::GCounter(b)) => {
+                c.handle_msg(b).map(NestedBody::GCounter)
+            }
+            (Entry::PnCounter(c), NestedBody::PnCounter(b)) => {
+                c.handle_msg(b).map(NestedBody::PnCounter)
+            }
+            (Entry::OrSet(c), NestedBody::OrSet(b)) => c.handle_msg(b).map(NestedBody::OrSet),
+            (Entry::LwwRegister(c), NestedBody::LwwRegister(b)) => {
+                c.handle_msg(b).map(NestedBody::LwwRegister)
+            }
+            _ => None,
+        }
+    }
+
+    fn read(&mut self) -> Value {
+        match self {
+            Entry::GCounter(c) => read_value(c, CounterBodyData::Read),
+            Entry::PnCounter(c) => read_value(c, CounterBodyData::Read),
+            Entry::OrSet(c) => read_value(c, OrSetBodyData::Read),
+            Entry::LwwRegister(c) => read_value(c, RegisterBodyData::Read),
+        }
+    }
+
+    /// Same kinds only, the map settles kind conflicts before.
+    fn merge(&mut self, state: &EntryState, delta: bool) {
+        match (self, state) {
+            (Entry::GCounter(c), EntryState::GCounter(s)) => merge_into(c, s, delta),
+            (Entry::PnCounter(c), EntryState::PnCounter(s)) => merge_into(c, s, delta),
+            (Entry::OrSet(c), EntryState::OrSet(s)) => merge_into(c, s, delta),
+            (Entry::LwwRegister(c), EntryState::LwwRegister(s)) => merge_into(c, s, delta),
+            (entry, state) => log::warn!(
+                "Not merging {:?} state into {:?}",
+                state.kind(),
+                entry.kind()
+            ),
+        }
+    }
+
+    fn get_state(&self) -> EntryState {
+        match self {
+            Entry::GCounter(c) => EntryState::GCounter(c.get_state()),
+            Entry::PnCounter(c) => EntryState::PnCounter(c.get_state()),
+            Entry::OrSet(c) => EntryState::OrSet(c.get_state()),
+            Entry::LwwRegister(c) => EntryState::LwwRegister(c.get_state()),
+        }
+    }
+
+    fn take_delta(&mut self) -> Option<EntryState> {
+        match self {
+            Entry::GCounter(c) => c.take_delta().map(EntryState::GCounter),
+            Entry::PnCounter(c) => c.take_delta().map(EntryState::PnCounter),
+            Entry::OrSet(c) => c.take_delta().map(EntryState::OrSet),
+            Entry::LwwRegister(c) => c.take_delta().map(EntryState::LwwRegister),
+        }
+    }
+}
+
+fn initialized<C: Crdt + Default>(node_id: &NodeId) -> C {
+    let mut crdt = C::default();
+    crdt.init(node_id);
+    crdt
+}
+
+fn read_value<C: Crdt>(crdt: &mut C, read: C::Body) -> Value {
+    let resp = serde_json::to_value(crdt.handle_msg(&read)).unwrap();
+    resp.get("value").cloned().unwrap_or(Value::Null)
+}
+
+fn merge_into<C: Crdt>(crdt: &mut C, state: &C::State, delta: bool) {
+    if delta {
+        crdt.merge_delta(state);
+    } else {
+        crdt.update(state);
+    }
+}
+
+/// Keys are never removed, an entry is created by the first write to it or
+/// the first state received for it.
+#[derive(Default)]
+struct CrdtMap {
+    node_id: NodeId,
+    entries: BTreeMap<MapKey, Entry>,
+    /// Keys written since the last delta.
+    touched: BTreeSet<MapKey>,
+    /// Keys created since the last delta, sent even if the write changed
+    /// nothing so that other nodes learn the kind.
+    created: BTreeSet<MapKey>,
+}
+
+impl CrdtMap {
+    fn apply(&mut self, key: &MapKey, body: &NestedBody) -> MapBodyData {
+        let kind = Kind::of_body(body);
+        let entry = match self.entries.entry(key.clone()) {
+            btree_map::Entry::Occupied(entry) => entry.into_mut(),
+            // Reading a missing key doesn't create it, it reads as an empty CRDT.
+            btree_map::Entry::Vacant(_) if body.is_read() => {
+                return match Entry::new(kind, &self.node_id).handle_msg(body) {
+                    Some(body) => MapBodyData::ApplyOk {
+                        key: key.clone(),
+                        body,
+                    },
+                    None => unexpected(key, body),
+                };
+            }
+            btree_map::Entry::Vacant(entry) => {
+                self.created.insert(key.clone());
+                entry.insert(Entry::new(kind, &self.node_id))
+            }
+        };
+        if entry.kind() != kind {
+            return MapBodyData::Error(ErrorData::new(
+                format!("Key {key} holds a {:?}, not a {kind:?}", entry.kind()),
+                ErrorCode::PreconditionFailed,
+            ));
+        }
+        match entry.handle_msg(body) {
+            Some(body) => {
+                self.touched.insert(key.clone());
+                MapBodyData::ApplyOk {
+                    key: key.clone(),
+                    body,
+                }
+            }
+            None => unexpected(key, body),
+        }
+    }
+
+    /// Recurses into the entries, a state of a later kind replaces the entry.
+    fn merge(&mut self, state: &MapState, delta: bool) {
+        for (key, theirs) in state {
+            let kind = theirs.kind();
+            match self.entries.get(key).map(Entry::kind) {
+                Some(ours) if ours > kind => continue,
+                Some(ours) if ours == kind => {}
+                _ => {
+                    self.entries
+                        .insert(key.clone(), Entry::new(kind, &self.node_id));
+                }
+            }
+            self.entries.get_mut(key).unwrap().merge(theirs, delta);
+        }
+    }
+}
+
+fn unexpected(key: &MapKey, body: &NestedBody) -> MapBodyData {
+    MapBodyData::Error(ErrorData::new(
+        format!("Unexpected message for key {key}: {body:?}"),
+        ErrorCode::NotSupported,
+    ))
+}
+
+impl Crdt for CrdtMap {
+    type Body = MapBodyData;
+    type State = MapState;
+
+    const DELTAS: bool = true;
+
+    fn init(&mut self, node_id: &NodeId) {
+        self.node_id = node_id.clone();
+    }
+
+    fn handle_msg(&mut self, body: &Self::Body) -> Option<Self::Body> {
+        match body {
+            MapBodyData::Apply { key, body } => Some(self.apply(key, body)),
+            MapBodyData::Read => Some(MapBodyData::ReadOk {
+                value: self
+                    .entries
+                    .iter_mut()
+                    .map(|(key, entry)| (key.clone(), entry.read()))
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, state: &Self::State) {
+        self.merge(state, false);
+    }
+
+    fn merge_delta(&mut self, delta: &Self::State) {
+        self.merge(delta, true);
+    }
+
+    fn get_state(&self) -> Self::State {
+        self.entries
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.get_state()))
+            .collect()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        let created = std::mem::take(&mut self.created);
+        let delta: MapState = std::mem::take(&mut self.touched)
+            .into_iter()
+            .filter_map(|key| {
+                let entry = self.entries.get_mut(&key)?;
+                let delta = entry
+                    .take_delta()
+                    .or_else(|| created.contains(&key).then(|| entry.get_state()))?;
+                Some((key, delta))
+            })
+            .collect();
+        (!delta.is_empty()).then_some(delta)
+    }
+}
+
+#[cfg(test)]
+mod map_tests {
+    use rand::{rngs::StdRng, Rng};
+    use serde_json::{json, Value};
+
+    use super::CrdtMap;
+    use crate::protocol::{crdts::map::MapBodyData, NodeId};
+    use crate::workloads::crdts::laws::{check_laws, TestCrdt};
+    use crate::workloads::crdts::Crdt;
+
+    impl TestCrdt for CrdtMap {
+        type Observed = Value;
+
+        fn replica(node_id: &NodeId) -> Self {
+            node(node_id)
+        }
+
+        /// Keys are written with random kinds to cover the conflicts too.
+        fn random_op(rng: &mut StdRng) -> MapBodyData {
+            let n = rng.gen_range(0..5);
+            let (crdt, body) = match rng.gen_range(0..5) {
+                0 => ("g-counter", json!({"type": "add", "delta": n})),
+                1 => ("pn-counter", json!({"type": "add", "delta": n - 2})),
+                2 => ("or-set", json!({"type": "add", "element": n})),
+                3 => ("or-set", json!({"type": "remove", "element": n})),
+                _ => ("lww-register", json!({"type": "write", "value": n})),
+            };
+            let key = format!("k{}", rng.gen_range(0..3));
+            let req = json!({"type": "apply", "key": key, "crdt": crdt, "body": body});
+            serde_json::from_value(req).unwrap()
+        }
+
+        fn observe(&mut self) -> Value {
+            read(self)
+        }
+    }
+
+    fn node(node_id: &str) -> CrdtMap {
+        let mut map = CrdtMap::default();
+        map.init(&node_id.to_owned());
+        map
+    }
+
+    fn apply(map: &mut CrdtMap, key: &str, crdt: &str, body: Value) -> Value {
+        let req = json!({"type": "apply", "key": key, "crdt": crdt, "body": body});
+        let resp = map.handle_msg(&serde_json::from_value(req).unwrap());
+        serde_json::to_value(resp.unwrap()).unwrap()
+    }
+
+    fn read(map: &mut CrdtMap) -> Value {
+        match map.handle_msg(&MapBodyData::Read) {
+            Some(MapBodyData::ReadOk { value }) => serde_json::to_value(value).unwrap(),
+            other => panic!("Expected read_ok, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn merges_recurse_into_values() {
+        let (mut n1, mut n2) = (node("n1"), node("n2"));
+        apply(
+            &mut n1,
+            "visits",
+            "g-counter",
+            json!({"type": "add", "delta": 2}),
+        );
+        apply(
+            &mut n1,
+            "tags",
+            "or-set",
+            json!({"type": "add", "element": 1}),
+        );
+        let delta = n1.take_delta().unwrap();
+        apply(
+            &mut n2,
+            "visits",
+            "g-counter",
+            json!({"type": "add", "delta": 3}),
+        );
+        apply(
+            &mut n2,
+            "tags",
+            "or-set",
+            json!({"type": "add", "element": 2}),
+        );
+        apply(
+            &mut n2,
+            "name",
+            "lww-register",
+            json!({"type": "write", "value": "x"}),
+        );
+        n2.merge_delta(&delta);
+        n1.update(&n2.get_state());
+
+        let expected = json!({"name": "x", "tags": [1, 2], "visits": 5});
+        assert_eq!(read(&mut n1), expected);
+        assert_eq!(read(&mut n2), expected);
+        assert_eq!(n1.take_delta(), None);
+        let resp = apply(&mut n1, "visits", "g-counter", json!({"type": "read"}));
+        assert_eq!(resp["body"], json!({"type": "read_ok", "value": 5}));
+    }
+
+    #[test]
+    fn conflicting_kinds_converge() {
+        let (mut n1, mut n2) = (node("n1"), node("n2"));
+        apply(
+            &mut n1,
+            "k",
+            "pn-counter",
+            json!({"type": "add", "delta": -1}),
+        );
+        apply(&mut n2, "k", "or-set", json!({"type": "add", "element": 7}));
+        let resp = apply(
+            &mut n2,
+            "k",
+            "pn-counter",
+            json!({"type": "add", "delta": 1}),
+        );
+        assert_eq!(resp["type"], "error");
+        assert_eq!(resp["code"], 22);
+
+        n1.update(&n2.get_state());
+        n2.update(&n1.get_state());
+        assert_eq!(read(&mut n1), json!({"k": [7]}));
+        assert_eq!(n1.get_state(), n2.get_state());
+
+        // Reading a missing key doesn't create it.
+        let resp = apply(&mut n1, "missing", "lww-register", json!({"type": "read"}));
+        assert_eq!(resp["body"], json!({"type": "read_ok", "value": null}));
+        assert_eq!(read(&mut n1), json!({"k": [7]}));
+    }
+
+    #[test]
+    fn new_keys_are_in_the_delta() {
+        let mut n1 = node("n1");
+        apply(
+            &mut n1,
+            "tags",
+            "or-set",
+            json!({"type": "remove", "element": 1}),
+        );
+        let delta = n1.take_delta().unwrap();
+        assert_eq!(
+            serde_json::to_value(delta).unwrap(),
+            json!({"tags": {"crdt": "or-set", "state": {"entries": [], "context": {"versions": {}, "dots": []}}}})
+        );
+        apply(
+            &mut n1,
+            "tags",
+            "or-set",
+            json!({"type": "remove", "element": 1}),
+        );
+        assert_eq!(n1.take_delta(), None);
+    }
+
+    #[test]
+    fn merge_laws() {
+        check_laws::<CrdtMap>();
+    }
+}
@@ -4,36 +4,116 @@ use std::{
 };
 
 use crate::{
+    cli::{CliError, OptionSpec, Options},
     io::{non_blocking::receive_msg, send_msg},
+    logging::{self, MsgSpan},
     protocol::{
-        crdts::{CommonBodyData, CrdtBody},
-        gen_next_msg_id, Body, NodeId,
+        crdts::{CommonBodyData, COMMON_MSG_TYPES},
+        gen_next_msg_id, Body, Message, NodeId, RawMessage,
     },
 };
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, Duration, Instant};
 
-pub mod g_set;
+use delta::{DeltaLog, Replication};
+use replication::{RateLimit, Strategy};
+
+pub mod bounded_counter;
+mod delta;
+mod dots;
 pub mod g_counter;
+pub mod g_set;
+#[cfg(test)]
+mod laws;
+pub mod map;
+pub mod or_set;
 pub mod pn_counter;
+pub mod registers;
+mod replication;
+#[cfg(test)]
+mod sim;
 
 pub trait Crdt {
     type Body: Serialize + DeserializeOwned + Debug;
-    type State: Serialize + DeserializeOwned + Debug;
+    type State: Serialize + DeserializeOwned + Debug + Clone + Send;
+
+    /// Whether [`Crdt::take_delta`] returns the local changes, otherwise the
+    /// full state is sent to every other node on each replication.
+    const DELTAS: bool = false;
 
     fn handle_msg(&mut self, body: &Self::Body) -> Option<Self::Body>;
     fn update(&mut self, state: &Self::State);
     fn get_state(&self) -> Self::State;
     fn init(&mut self, _node_id: &NodeId) {}
+
+    /// Local changes since the previous call, as a state that is merged into
+    /// the full one. Changes merged by `update` are left out, but the ones it
+    /// makes in response to them are included.
+    fn take_delta(&mut self) -> Option<Self::State> {
+        None
+    }
+
+    fn merge_delta(&mut self, delta: &Self::State) {
+        self.update(delta);
+    }
+}
+
+pub static OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "replication-interval-ms",
+        description: "Delay between replication rounds",
+        default: "5000",
+    },
+    OptionSpec {
+        name: "replication",
+        description: "`broadcast` sends to every other node, `fanout` to random ones, `push-pull` sends digests to random ones and states only where they differ",
+        default: "broadcast",
+    },
+    OptionSpec {
+        name: "fanout",
+        description: "Nodes reached by a round of the fanout and push-pull replication",
+        default: "2",
+    },
+    OptionSpec {
+        name: "eager-rounds-per-sec",
+        description: "Extra replication rounds right after writes allowed per second, 0 disables them",
+        default: "0",
+    },
+    OptionSpec {
+        name: "max-deltas",
+        description: "Deltas kept for replication, nodes further behind get the full state",
+        default: "1000",
+    },
+];
+
+pub struct Config {
+    replication_interval: Duration,
+    strategy: Strategy,
+    fanout: usize,
+    eager_rounds_per_sec: u32,
+    max_deltas: usize,
+}
+
+impl Config {
+    pub fn from_options(options: &Options) -> Result<Self, CliError> {
+        Ok(Self {
+            replication_interval: options.millis("replication-interval-ms")?,
+            strategy: options.get("replication")?,
+            fanout: options.get("fanout")?,
+            eager_rounds_per_sec: options.get("eager-rounds-per-sec")?,
+            max_deltas: options.get("max-deltas")?,
+        })
+    }
 }
 
-pub fn run<C: Crdt + Send + 'static>(crdt: C) {
+pub fn run<C: Crdt + Send + 'static>(crdt: C, config: Config) {
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .unwrap()
         .block_on(async move {
-            CrdtNode::run(crdt).await;
+            CrdtNode::run(crdt, config).await;
         });
 }
 
@@ -43,91 +123,250 @@ struct NodeConfig {
     node_ids: Vec<NodeId>,
 }
 
-type Message<C> = crate::protocol::crdts::Message<<C as Crdt>::Body, <C as Crdt>::State>;
+impl NodeConfig {
+    fn neighbours(&self) -> Vec<NodeId> {
+        self.node_ids
+            .iter()
+            .filter(|&id| *id != self.node_id)
+            .cloned()
+            .collect()
+    }
+}
+
+type CommonMessage<C> = Message<CommonBodyData<<C as Crdt>::State>>;
+type CustomMessage<C> = Message<<C as Crdt>::Body>;
+
+struct NodeState<C: Crdt> {
+    crdt: C,
+    log: DeltaLog<C::State>,
+}
 
 struct CrdtNode<C: Crdt> {
     config: NodeConfig,
-    crdt: Arc<Mutex<C>>,
+    replication_interval: Duration,
+    strategy: Strategy,
+    fanout: usize,
+    state: Arc<Mutex<NodeState<C>>>,
+    /// Picks the nodes of the fanout and push-pull rounds.
+    rng: Mutex<StdRng>,
+    eager_rounds: Mutex<RateLimit>,
 }
 
 impl<C: Crdt + Send + 'static> CrdtNode<C> {
-    pub async fn run(mut crdt: C) {
-        let config = Self::init_node().await;
-        eprintln!("Node init done: {:?}", config);
-        crdt.init(&config.node_id);
-        let node = CrdtNode {
-            config,
-            crdt: Arc::new(Mutex::new(crdt)),
-        };
+    pub async fn run(crdt: C, config: Config) {
+        let node_config = Self::init_node().await;
+        log::info!("Node init done: {:?}", node_config);
+        let node = Arc::new(Self::new(
+            crdt,
+            &config,
+            node_config,
+            StdRng::from_entropy(),
+        ));
         node.start_replication();
         loop {
             node.handle_next_msg().await;
         }
     }
 
+    fn new(mut crdt: C, config: &Config, node_config: NodeConfig, rng: StdRng) -> Self {
+        crdt.init(&node_config.node_id);
+        let log = DeltaLog::new(&node_config.neighbours(), config.max_deltas);
+        CrdtNode {
+            config: node_config,
+            replication_interval: config.replication_interval,
+            strategy: config.strategy,
+            fanout: config.fanout,
+            state: Arc::new(Mutex::new(NodeState { crdt, log })),
+            rng: Mutex::new(rng),
+            eager_rounds: Mutex::new(RateLimit::new(config.eager_rounds_per_sec)),
+        }
+    }
+
     async fn handle_next_msg(&self) {
-        let msg: Message<C> = receive_msg().await;
-        match msg.body.data {
-            CrdtBody::Common(CommonBodyData::Replicate { ref state }) => {
-                self.crdt.lock().unwrap().update(state);
+        let raw: RawMessage = receive_msg().await;
+        MsgSpan::of_raw(&raw).sync_scope(|| self.handle_msg(raw));
+    }
+
+    fn handle_msg(&self, raw: RawMessage) {
+        let is_common = raw
+            .msg_type()
+            .is_some_and(|t| COMMON_MSG_TYPES.contains(&t));
+        if is_common {
+            match raw.decode::<CommonBodyData<C::State>>() {
+                Ok(msg) => self.handle_common_msg(msg),
+                Err(err) => log::warn!("Failed to decode {:?}: {err}", raw),
             }
-            CrdtBody::Custom(ref body) => {
-                let resp_body = { self.crdt.lock().unwrap().handle_msg(body) };
-                match resp_body {
-                    Some(resp_body) => {
-                        let resp: Message<C> = msg.create_response(CrdtBody::Custom(resp_body));
-                        send_msg(&resp);
-                    }
-                    None => {
-                        eprintln!("No response to {:?}", msg);
-                    }
+            return;
+        }
+        let msg: CustomMessage<C> = match raw.decode() {
+            Ok(msg) => msg,
+            Err(err) => {
+                log::warn!("Failed to decode {:?}: {err}", raw);
+                return;
+            }
+        };
+        let (resp_body, changed) = {
+            let mut state = self.state.lock().unwrap();
+            let resp_body = state.crdt.handle_msg(&msg.body.data);
+            (resp_body, self.record_delta(&mut state))
+        };
+        match resp_body {
+            Some(resp_body) => send_msg(&msg.create_response(resp_body)),
+            None => log::warn!("No response to {:?}", msg),
+        }
+        if changed && self.eager_round_allowed() {
+            self.tick();
+        }
+    }
+
+    /// Logs the changes the CRDT made itself, returns whether there were any.
+    fn record_delta(&self, state: &mut NodeState<C>) -> bool {
+        let Some(delta) = state.crdt.take_delta() else {
+            return false;
+        };
+        // Push-pull rounds only exchange full states.
+        if self.strategy != Strategy::PushPull {
+            state.log.push(delta);
+        }
+        true
+    }
+
+    /// A write starts a replication round right away unless too many did.
+    fn eager_round_allowed(&self) -> bool {
+        self.eager_rounds
+            .lock()
+            .unwrap()
+            .try_acquire(Instant::now())
+    }
+
+    fn handle_common_msg(&self, msg: CommonMessage<C>) {
+        let mut state = self.state.lock().unwrap();
+        let resp = match msg.body.data {
+            CommonBodyData::Replicate {
+                state: ref full,
+                version,
+            } => {
+                state.crdt.update(full);
+                version.map(|version| CommonBodyData::ReplicateOk { version })
+            }
+            CommonBodyData::ReplicateDelta {
+                ref deltas,
+                version,
+            } => {
+                for delta in deltas {
+                    state.crdt.merge_delta(delta);
                 }
+                Some(CommonBodyData::ReplicateOk { version })
+            }
+            CommonBodyData::ReplicateOk { version } => {
+                state.log.acked(&msg.src, version);
+                None
             }
-            _ => eprintln!("Ignoring unexpected message {:?}", msg),
+            CommonBodyData::ReplicateDigest { digest } => {
+                let ours = state.crdt.get_state();
+                (replication::digest(&ours) != digest)
+                    .then_some(CommonBodyData::ReplicatePull { state: ours })
+            }
+            CommonBodyData::ReplicatePull { state: ref theirs } => {
+                state.crdt.update(theirs);
+                Some(CommonBodyData::Replicate {
+                    state: state.crdt.get_state(),
+                    version: None,
+                })
+            }
+            _ => {
+                log::warn!("Ignoring unexpected message {:?}", msg);
+                None
+            }
+        };
+        self.record_delta(&mut state);
+        drop(state);
+        if let Some(resp) = resp {
+            let resp: CommonMessage<C> = msg.create_response(resp);
+            send_msg(&resp);
         }
     }
 
-    fn start_replication(&self) {
-        let node_id = self.config.node_id.clone();
-        let neighbours: Vec<_> = self
-            .config
-            .node_ids
-            .iter()
-            .cloned()
-            .filter(|id| *id != node_id)
-            .collect();
-        let crdt = self.crdt.clone();
+    fn start_replication(self: &Arc<Self>) {
+        let node = self.clone();
         tokio::spawn(async move {
-            eprintln!("Starting replication for node {node_id}");
+            log::info!("Starting replication for node {}", node.config.node_id);
             loop {
-                for neighbour in &neighbours {
-                    Self::replicate_state(&node_id, neighbour, &crdt);
-                }
-                sleep(Duration::from_secs(5)).await;
+                node.tick();
+                sleep(node.replication_interval).await;
             }
         });
     }
 
-    fn replicate_state(src: &String, dest: &String, crdt: &Mutex<C>) {
-        let state = { crdt.lock().unwrap().get_state() };
-        let msg = Message::<C> {
-            src: src.clone(),
-            dest: dest.clone(),
+    /// One round of replication, to the nodes picked by the strategy.
+    fn tick(&self) {
+        let mut targets = self.config.neighbours();
+        if self.strategy != Strategy::Broadcast {
+            targets.shuffle(&mut *self.rng.lock().unwrap());
+            targets.truncate(self.fanout);
+        }
+        for dest in &targets {
+            match self.strategy {
+                Strategy::PushPull => self.send_digest(dest),
+                Strategy::Broadcast | Strategy::Fanout => self.replicate(dest),
+            }
+        }
+    }
+
+    fn send_digest(&self, dest: &str) {
+        let digest = replication::digest(&self.state.lock().unwrap().crdt.get_state());
+        self.send(dest, CommonBodyData::ReplicateDigest { digest });
+    }
+
+    /// Sends the deltas the neighbour hasn't acknowledged, or the full state
+    /// if the CRDT has no deltas or some of them were dropped already.
+    fn replicate(&self, dest: &str) {
+        let data = {
+            let state = self.state.lock().unwrap();
+            if !C::DELTAS {
+                CommonBodyData::Replicate {
+                    state: state.crdt.get_state(),
+                    version: None,
+                }
+            } else {
+                match state.log.next_for(dest) {
+                    Replication::UpToDate => return,
+                    Replication::Deltas { version, deltas } => CommonBodyData::ReplicateDelta {
+                        deltas: deltas.into_iter().cloned().collect(),
+                        version,
+                    },
+                    Replication::FullState { version } => {
+                        log::info!("Sending full state to {dest}, it's behind the kept deltas");
+                        CommonBodyData::Replicate {
+                            state: state.crdt.get_state(),
+                            version: Some(version),
+                        }
+                    }
+                }
+            }
+        };
+        self.send(dest, data);
+    }
+
+    fn send(&self, dest: &str, data: CommonBodyData<C::State>) {
+        let msg = CommonMessage::<C> {
+            src: self.config.node_id.clone(),
+            dest: dest.to_owned(),
             body: Body {
                 msg_id: Some(gen_next_msg_id()),
                 in_reply_to: None,
-                data: CrdtBody::Common(CommonBodyData::Replicate { state }),
+                data,
             },
         };
         send_msg(&msg);
     }
 
     async fn init_node() -> NodeConfig {
-        let init_msg: Message<C> = receive_msg().await;
+        let init_msg: CommonMessage<C> = receive_msg().await;
         match init_msg.body.data {
-            CrdtBody::Common(CommonBodyData::Init(ref data)) => {
-                let resp: Message<C> =
-                    init_msg.create_response(CrdtBody::Common(CommonBodyData::InitOk));
+            CommonBodyData::Init(ref data) => {
+                logging::set_node_id(&data.node_id);
+                let resp: CommonMessage<C> = init_msg.create_response(CommonBodyData::InitOk);
                 send_msg(&resp);
                 NodeConfig {
                     node_id: data.node_id.clone(),
@@ -0,0 +1,334 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::dots::{Dot, DotContext};
+use super::{Config, Crdt, OPTIONS};
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::protocol::{crdts::or_set::*, NodeId};
+use crate::workloads::Workload;
+
+pub struct OrSetWorkload;
+
+impl Workload for OrSetWorkload {
+    fn name(&self) -> &'static str {
+        "or-set"
+    }
+
+    fn description(&self) -> &'static str {
+        "Observed-remove set CRDT, Maelstrom only checks its adds and reads"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("g-set")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
+    log::info!("Running OR-Set workload");
+    super::run(OrSet::default(), config);
+}
+
+/// Every add is tagged with a new dot, a remove drops the dots of the element
+/// seen so far. A merge keeps a dot unless the other side has seen and dropped
+/// it, so removed elements leave no tombstones besides the dot context.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct OrSetState {
+    #[serde(with = "entry_pairs")]
+    entries: BTreeMap<ElementValue, BTreeSet<Dot>>,
+    context: DotContext,
+}
+
+impl OrSetState {
+    /// Returns the delta of the add, which replaces the element's previous dots.
+    pub fn add(&mut self, node_id: &NodeId, element: ElementValue) -> OrSetState {
+        let dot = self.context.next_dot(node_id);
+        let replaced = self
+            .entries
+            .insert(element, BTreeSet::from([dot.clone()]))
+            .unwrap_or_default();
+        OrSetState {
+            entries: BTreeMap::from([(element, BTreeSet::from([dot.clone()]))]),
+            context: DotContext::from_dots(replaced.into_iter().chain([dot])),
+        }
+    }
+
+    /// Returns the delta of the remove, empty if the element isn't there.
+    pub fn remove(&mut self, element: ElementValue) -> OrSetState {
+        OrSetState {
+            entries: BTreeMap::new(),
+            context: DotContext::from_dots(self.entries.remove(&element).unwrap_or_default()),
+        }
+    }
+
+    pub fn merge(&mut self, other: &OrSetState) {
+        let elements: BTreeSet<_> = self
+            .entries
+            .keys()
+            .chain(other.entries.keys())
+            .copied()
+            .collect();
+        for element in elements {
+            let empty = BTreeSet::new();
+            let ours = self.entries.get(&element).unwrap_or(&empty);
+            let theirs = other.entries.get(&element).unwrap_or(&empty);
+            let dots: BTreeSet<_> = ours
+                .iter()
+                .filter(|dot| theirs.contains(dot) || !other.context.contains(dot))
+                .chain(theirs.iter().filter(|dot| !self.context.contains(dot)))
+                .cloned()
+                .collect();
+            if dots.is_empty() {
+                self.entries.remove(&element);
+            } else {
+                self.entries.insert(element, dots);
+            }
+        }
+        self.context.merge(&other.context);
+    }
+
+    pub fn elements(&self) -> Vec<ElementValue> {
+        self.entries.keys().copied().collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.context.is_empty()
+    }
+}
+
+/// Entries are sent as `[element, dots]` pairs, JSON object keys are strings
+/// and the tagged message bodies don't parse them back into integers.
+mod entry_pairs {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::{Dot, ElementValue};
+
+    type Entries = BTreeMap<ElementValue, BTreeSet<Dot>>;
+
+    pub fn serialize<S: Serializer>(entries: &Entries, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(entries)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Entries, D::Error> {
+        Ok(
+            Vec::<(ElementValue, BTreeSet<Dot>)>::deserialize(deserializer)?
+                .into_iter()
+                .collect(),
+        )
+    }
+}
+
+#[derive(Default)]
+pub(super) struct OrSet {
+    node_id: NodeId,
+    state: OrSetState,
+    /// Join of the deltas since the last `take_delta`.
+    delta: Option<OrSetState>,
+}
+
+impl OrSet {
+    fn record(&mut self, delta: OrSetState) {
+        if delta.is_empty() {
+            return;
+        }
+        match self.delta {
+            Some(ref mut pending) => pending.merge(&delta),
+            None => self.delta = Some(delta),
+        }
+    }
+}
+
+impl Crdt for OrSet {
+    type Body = OrSetBodyData;
+    type State = OrSetState;
+
+    const DELTAS: bool = true;
+
+    fn init(&mut self, node_id: &NodeId) {
+        self.node_id = node_id.clone();
+    }
+
+    fn handle_msg(&mut self, body: &Self::Body) -> Option<Self::Body> {
+        match body {
+            OrSetBodyData::Add { element } => {
+                let delta = self.state.add(&self.node_id, *element);
+                self.record(delta);
+                Some(OrSetBodyData::AddOk)
+            }
+            OrSetBodyData::Remove { element } => {
+                let delta = self.state.remove(*element);
+                self.record(delta);
+                Some(OrSetBodyData::RemoveOk)
+            }
+            OrSetBodyData::Read => Some(OrSetBodyData::ReadOk {
+                value: self.state.elements(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, state: &Self::State) {
+        self.state.merge(state);
+    }
+
+    fn get_state(&self) -> Self::State {
+        self.state.clone()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        self.delta.take()
+    }
+}
+
+#[cfg(test)]
+mod or_set_tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{OrSet, OrSetState};
+    use crate::protocol::{
+        crdts::{or_set::*, CommonBodyData},
+        NodeId,
+    };
+    use crate::workloads::crdts::laws::{check_laws, TestCrdt};
+    use crate::workloads::crdts::Crdt;
+
+    impl TestCrdt for OrSet {
+        type Observed = OrSetState;
+
+        fn replica(node_id: &NodeId) -> Self {
+            let mut set = OrSet::default();
+            set.init(node_id);
+            set
+        }
+
+        fn random_op(rng: &mut StdRng) -> OrSetBodyData {
+            let element = rng.gen_range(0..5);
+            if rng.gen_range(0..3) == 0 {
+                OrSetBodyData::Remove { element }
+            } else {
+                OrSetBodyData::Add { element }
+            }
+        }
+
+        fn observe(&mut self) -> Self::Observed {
+            self.get_state()
+        }
+    }
+
+    fn merged(a: &OrSetState, b: &OrSetState) -> OrSetState {
+        let mut a = a.clone();
+        a.merge(b);
+        a
+    }
+
+    #[test]
+    fn concurrent_add_wins() {
+        let (n1, n2) = ("n1".to_owned(), "n2".to_owned());
+        let mut a = OrSetState::default();
+        a.add(&n1, 1);
+        let mut b = a.clone();
+        b.remove(1);
+        a.add(&n1, 1);
+        a.merge(&b);
+        b.merge(&a);
+        assert_eq!(a.elements(), vec![1]);
+        assert_eq!(a, b);
+
+        b.add(&n2, 2);
+        b.remove(1);
+        a.merge(&b);
+        assert_eq!(a.elements(), vec![2]);
+    }
+
+    #[test]
+    fn deltas_rebuild_the_state() {
+        let (n1, n2) = ("n1".to_owned(), "n2".to_owned());
+        let mut a = OrSetState::default();
+        let mut b = OrSetState::default();
+        let mut from_a = OrSetState::default();
+        for delta in [a.add(&n1, 1), a.add(&n1, 2), a.add(&n1, 1), a.remove(2)] {
+            b.merge(&delta);
+            from_a.merge(&delta);
+        }
+        let delta = b.add(&n2, 3);
+        a.merge(&delta);
+        assert_eq!(from_a.elements(), vec![1]);
+        assert_eq!(a, b);
+        assert_eq!(b.elements(), vec![1, 3]);
+    }
+
+    #[test]
+    fn state_decodes_from_replicate() {
+        let mut state = OrSetState::default();
+        state.add(&"n1".to_owned(), 1);
+        state.add(&"n2".to_owned(), -2);
+        state.remove(1);
+        state.add(&"n1".to_owned(), 3);
+        let line = serde_json::to_string(&CommonBodyData::Replicate {
+            state: state.clone(),
+            version: None,
+        })
+        .unwrap();
+        match serde_json::from_str::<CommonBodyData<OrSetState>>(&line).unwrap() {
+            CommonBodyData::Replicate { state: decoded, .. } => assert_eq!(decoded, state),
+            other => panic!("Unexpected body {other:?}"),
+        }
+    }
+
+    /// States of replicas doing random adds and removes and merging each
+    /// other's states now and then.
+    fn random_states(seed: u64) -> Vec<OrSetState> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let nodes: Vec<_> = (0..3).map(|i| format!("n{i}")).collect();
+        let mut replicas = vec![OrSetState::default(); nodes.len()];
+        let mut states = Vec::new();
+        for _ in 0..60 {
+            let i = rng.gen_range(0..nodes.len());
+            let element = rng.gen_range(0..5);
+            match rng.gen_range(0..4) {
+                0 | 1 => {
+                    replicas[i].add(&nodes[i], element);
+                }
+                2 => {
+                    replicas[i].remove(element);
+                }
+                _ => {
+                    let other = replicas[rng.gen_range(0..nodes.len())].clone();
+                    replicas[i].merge(&other);
+                }
+            }
+            states.push(replicas[i].clone());
+        }
+        states
+    }
+
+    #[test]
+    fn merge_is_a_join() {
+        for seed in 0..20 {
+            let states = random_states(seed);
+            for a in states.iter().step_by(7) {
+                assert_eq!(&merged(a, a), a);
+                for b in states.iter().step_by(5) {
+                    assert_eq!(merged(a, b), merged(b, a));
+                    for c in states.iter().step_by(11) {
+                        assert_eq!(merged(&merged(a, b), c), merged(a, &merged(b, c)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn merge_laws() {
+        check_laws::<OrSet>();
+    }
+}
@@ -1,33 +1,69 @@
 use std::collections::HashMap;
 
-use super::Crdt;
+use super::{Config, Crdt, OPTIONS};
+use crate::cli::{CliError, OptionSpec, Options};
 use crate::protocol::{crdts::counter::*, NodeId};
+use crate::workloads::Workload;
 
-pub fn run() {
-    eprintln!("Running PN-Counter workload");
-    super::run(PnCounter {
-        node_id: None,
-        values: HashMap::new(),
-    });
+pub struct PnCounterWorkload;
+
+impl Workload for PnCounterWorkload {
+    fn name(&self) -> &'static str {
+        "pn-counter"
+    }
+
+    fn description(&self) -> &'static str {
+        "Increment and decrement counter CRDT"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("pn-counter")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
+    log::info!("Running PN-Counter workload");
+    super::run(
+        PnCounter {
+            node_id: None,
+            values: HashMap::new(),
+            changed: false,
+        },
+        config,
+    );
 }
 
 type CounterValue = i64;
 
-#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
-struct CounterState {
-    pos: u64,
-    neg: u64,
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(super) struct CounterState {
+    pub(super) pos: u64,
+    pub(super) neg: u64,
 }
 
-struct PnCounter {
+#[derive(Default)]
+pub(super) struct PnCounter {
     node_id: Option<String>,
     values: HashMap<NodeId, CounterState>,
+    /// The own entry changed since the last delta.
+    changed: bool,
 }
 
 impl Crdt for PnCounter {
     type Body = CounterBodyData<CounterValue>;
     type State = HashMap<String, CounterState>;
 
+    const DELTAS: bool = true;
+
     fn init(&mut self, node_id: &NodeId) {
         self.node_id = Some(node_id.clone());
         self.values.insert(node_id.clone(), CounterState::default());
@@ -42,6 +78,7 @@ impl Crdt for PnCounter {
                 } else {
                     state.neg += delta.unsigned_abs();
                 }
+                self.changed = true;
                 Some(CounterBodyData::AddOk)
             }
             CounterBodyData::Read => Some(CounterBodyData::ReadOk {
@@ -66,4 +103,54 @@ impl Crdt for PnCounter {
     fn get_state(&self) -> Self::State {
         self.values.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
     }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        if !std::mem::take(&mut self.changed) {
+            return None;
+        }
+        let node_id = self.node_id.as_ref().unwrap();
+        Some(HashMap::from([(node_id.clone(), self.values[node_id].clone())]))
+    }
+}
+
+#[cfg(test)]
+mod pn_counter_tests {
+    use std::collections::BTreeMap;
+
+    use rand::{rngs::StdRng, Rng};
+
+    use super::{CounterState, CounterValue, PnCounter};
+    use crate::protocol::{crdts::counter::*, NodeId};
+    use crate::workloads::crdts::laws::{check_laws, TestCrdt};
+    use crate::workloads::crdts::Crdt;
+
+    impl TestCrdt for PnCounter {
+        /// Nodes that haven't added anything may be missing.
+        type Observed = BTreeMap<NodeId, (u64, u64)>;
+
+        fn replica(node_id: &NodeId) -> Self {
+            let mut counter = PnCounter::default();
+            counter.init(node_id);
+            counter
+        }
+
+        fn random_op(rng: &mut StdRng) -> CounterBodyData<CounterValue> {
+            CounterBodyData::Add {
+                delta: rng.gen_range(-5..5),
+            }
+        }
+
+        fn observe(&mut self) -> Self::Observed {
+            self.get_state()
+                .into_iter()
+                .filter(|(_, state)| *state != CounterState::default())
+                .map(|(node_id, state)| (node_id, (state.pos, state.neg)))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn merge_laws() {
+        check_laws::<PnCounter>();
+    }
 }
@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Config, Crdt, OPTIONS};
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::protocol::{crdts::register::*, NodeId};
+use crate::workloads::Workload;
+
+pub struct LwwRegisterWorkload;
+
+impl Workload for LwwRegisterWorkload {
+    fn name(&self) -> &'static str {
+        "lww-register"
+    }
+
+    fn description(&self) -> &'static str {
+        "Last writer wins register CRDT ordered by hybrid logical clocks"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        log::info!("Running LWW-Register workload");
+        super::run(LwwRegister::default(), Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub struct MvRegisterWorkload;
+
+impl Workload for MvRegisterWorkload {
+    fn name(&self) -> &'static str {
+        "mv-register"
+    }
+
+    fn description(&self) -> &'static str {
+        "Multi-value register CRDT, reads return every concurrently written value"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        log::info!("Running MV-Register workload");
+        super::run(MvRegister::default(), Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+fn wall_clock_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Clock before unix epoch")
+        .as_millis() as u64
+}
+
+/// Hybrid logical clock timestamp, ties are broken by the node id.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Timestamp {
+    millis: u64,
+    counter: u64,
+    node_id: NodeId,
+}
+
+/// Follows the wall clock, but every timestamp it makes is above all the
+/// timestamps it made or observed before, even if the wall clock goes back.
+#[derive(Debug, Default)]
+struct Hlc {
+    millis: u64,
+    counter: u64,
+}
+
+impl Hlc {
+    fn tick(&mut self, now_ms: u64, node_id: &NodeId) -> Timestamp {
+        if now_ms > self.millis {
+            self.millis = now_ms;
+            self.counter = 0;
+        } else {
+            self.counter += 1;
+        }
+        Timestamp {
+            millis: self.millis,
+            counter: self.counter,
+            node_id: node_id.clone(),
+        }
+    }
+
+    fn observe(&mut self, ts: &Timestamp) {
+        if (ts.millis, ts.counter) > (self.millis, self.counter) {
+            self.millis = ts.millis;
+            self.counter = ts.counter;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stamped {
+    timestamp: Timestamp,
+    value: RegisterValue,
+}
+
+#[derive(Default)]
+pub(super) struct LwwRegister {
+    node_id: NodeId,
+    clock: Hlc,
+    state: Option<Stamped>,
+    changed: bool,
+}
+
+impl LwwRegister {
+    fn write(&mut self, value: RegisterValue, now_ms: u64) {
+        self.state = Some(Stamped {
+            timestamp: self.clock.tick(now_ms, &self.node_id),
+            value,
+        });
+        self.changed = true;
+    }
+}
+
+impl Crdt for LwwRegister {
+    type Body = RegisterBodyData<Option<RegisterValue>>;
+    type State = Option<Stamped>;
+
+    const DELTAS: bool = true;
+
+    fn init(&mut self, node_id: &NodeId) {
+        self.node_id = node_id.clone();
+    }
+
+    fn handle_msg(&mut self, body: &Self::Body) -> Option<Self::Body> {
+        match body {
+            RegisterBodyData::Write { value } => {
+                self.write(value.clone(), wall_clock_ms());
+                Some(RegisterBodyData::WriteOk)
+            }
+            RegisterBodyData::Read => Some(RegisterBodyData::ReadOk {
+                value: self.state.as_ref().map(|s| s.value.clone()),
+            }),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, state: &Self::State) {
+        let Some(theirs) = state else {
+            return;
+        };
+        self.clock.observe(&theirs.timestamp);
+        if self
+            .state
+            .as_ref()
+            .is_none_or(|ours| ours.timestamp < theirs.timestamp)
+        {
+            self.state = Some(theirs.clone());
+        }
+    }
+
+    fn get_state(&self) -> Self::State {
+        self.state.clone()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        std::mem::take(&mut self.changed).then(|| self.state.clone())
+    }
+}
+
+pub type VersionVector = BTreeMap<NodeId, u64>;
+
+/// `a` has seen every write `b` has seen.
+fn descends(a: &VersionVector, b: &VersionVector) -> bool {
+    b.iter()
+        .all(|(node_id, version)| a.get(node_id).is_some_and(|v| v >= version))
+}
+
+/// Concurrent writes, none of them has seen another one. Sorted by version
+/// vector, which identifies a write.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MvRegisterState {
+    siblings: Vec<(VersionVector, RegisterValue)>,
+}
+
+impl MvRegisterState {
+    /// Replaces every sibling, the write has seen all of them.
+    fn write(&mut self, node_id: &NodeId, value: RegisterValue) {
+        let mut version = VersionVector::new();
+        for (vv, _) in &self.siblings {
+            for (id, &v) in vv {
+                let ours = version.entry(id.clone()).or_default();
+                *ours = (*ours).max(v);
+            }
+        }
+        *version.entry(node_id.clone()).or_default() += 1;
+        self.siblings = vec![(version, value)];
+    }
+
+    fn merge(&mut self, other: &MvRegisterState) {
+        let mut siblings: Vec<_> = self
+            .siblings
+            .iter()
+            .chain(&other.siblings)
+            .filter(|(vv, _)| {
+                !self
+                    .siblings
+                    .iter()
+                    .chain(&other.siblings)
+                    .any(|(other_vv, _)| other_vv != vv && descends(other_vv, vv))
+            })
+            .cloned()
+            .collect();
+        siblings.sort_by(|a, b| a.0.cmp(&b.0));
+        siblings.dedup_by(|a, b| a.0 == b.0);
+        self.siblings = siblings;
+    }
+
+    fn values(&self) -> Vec<RegisterValue> {
+        self.siblings
+            .iter()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct MvRegister {
+    node_id: NodeId,
+    state: MvRegisterState,
+    changed: bool,
+}
+
+impl Crdt for MvRegister {
+    type Body = RegisterBodyData<Vec<RegisterValue>>;
+    type State = MvRegisterState;
+
+    const DELTAS: bool = true;
+
+    fn init(&mut self, node_id: &NodeId) {
+        self.node_id = node_id.clone();
+    }
+
+    fn handle_msg(&mut self, body: &Self::Body) -> Option<Self::Body> {
+        match body {
+            RegisterBodyData::Write { value } => {
+                self.state.write(&self.node_id, value.clone());
+                self.changed = true;
+                Some(RegisterBodyData::WriteOk)
+            }
+            RegisterBodyData::Read => Some(RegisterBodyData::ReadOk {
+                value: self.state.values(),
+            }),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, state: &Self::State) {
+        self.state.merge(state);
+    }
+
+    fn get_state(&self) -> Self::State {
+        self.state.clone()
+    }
+
+    fn take_delta(&mut self) -> Option<Self::State> {
+        std::mem::take(&mut self.changed).then(|| self.state.clone())
+    }
+}
+
+#[cfg(test)]
+mod registers_tests {
+    use rand::{rngs::StdRng, Rng};
+    use serde_json::json;
+
+    use super::{LwwRegister, MvRegister, MvRegisterState, Stamped};
+    use crate::protocol::{crdts::register::*, NodeId};
+    use crate::workloads::crdts::laws::{check_laws, TestCrdt};
+    use crate::workloads::crdts::Crdt;
+
+    fn random_write<R>(rng: &mut StdRng) -> RegisterBodyData<R> {
+        RegisterBodyData::Write {
+            value: json!(rng.gen_range(0..10)),
+        }
+    }
+
+    impl TestCrdt for LwwRegister {
+        type Observed = Option<Stamped>;
+
+        fn replica(node_id: &NodeId) -> Self {
+            lww(node_id)
+        }
+
+        fn random_op(rng: &mut StdRng) -> Self::Body {
+            random_write(rng)
+        }
+
+        fn observe(&mut self) -> Self::Observed {
+            self.get_state()
+        }
+    }
+
+    impl TestCrdt for MvRegister {
+        type Observed = MvRegisterState;
+
+        fn replica(node_id: &NodeId) -> Self {
+            let mut register = MvRegister::default();
+            register.init(node_id);
+            register
+        }
+
+        fn random_op(rng: &mut StdRng) -> Self::Body {
+            random_write(rng)
+        }
+
+        fn observe(&mut self) -> Self::Observed {
+            self.get_state()
+        }
+    }
+
+    fn lww(node_id: &str) -> LwwRegister {
+        let mut register = LwwRegister::default();
+        register.init(&node_id.to_owned());
+        register
+    }
+
+    #[test]
+    fn lww_converges_on_latest_write() {
+        let (mut n1, mut n2) = (lww("n1"), lww("n2"));
+        n1.write(json!(1), 100);
+        n2.write(json!(2), 100);
+        n1.update(&n2.get_state());
+        n2.update(&n1.get_state());
+        assert_eq!(n1.get_state(), n2.get_state());
+        assert_eq!(n1.get_state().unwrap().value, json!(2));
+
+        // The wall clock of n1 is behind, its write still wins after seeing n2's.
+        n2.write(json!(3), 200);
+        n1.update(&n2.get_state());
+        n1.write(json!(4), 150);
+        n2.update(&n1.get_state());
+        assert_eq!(n2.get_state().unwrap().value, json!(4));
+        assert_eq!(n1.get_state(), n2.get_state());
+    }
+
+    #[test]
+    fn mv_keeps_concurrent_writes() {
+        let (n1, n2) = ("n1".to_owned(), "n2".to_owned());
+        let mut a = MvRegisterState::default();
+        let mut b = MvRegisterState::default();
+        a.write(&n1, json!(1));
+        b.write(&n2, json!(2));
+        let mut merged_a = a.clone();
+        merged_a.merge(&b);
+        b.merge(&a);
+        assert_eq!(merged_a, b);
+        assert_eq!(b.values(), vec![json!(1), json!(2)]);
+
+        // A write after seeing both replaces them everywhere.
+        b.write(&n2, json!(3));
+        merged_a.merge(&b);
+        a.merge(&b);
+        assert_eq!(merged_a.values(), vec![json!(3)]);
+        assert_eq!(a, merged_a);
+        a.merge(&a.clone());
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn merge_laws() {
+        check_laws::<LwwRegister>();
+        check_laws::<MvRegister>();
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use serde::Serialize;
+use tokio::time::{Duration, Instant};
+
+use crate::protocol::crdts::StateDigest;
+
+/// Which nodes a replication round reaches and what it sends them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    /// Deltas or the full state to every other node.
+    Broadcast,
+    /// Deltas or the full state to a few random nodes.
+    Fanout,
+    /// A digest to a few random nodes, states are only exchanged with the
+    /// ones that differ.
+    PushPull,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "broadcast" => Ok(Strategy::Broadcast),
+            "fanout" => Ok(Strategy::Fanout),
+            "push-pull" => Ok(Strategy::PushPull),
+            other => Err(format!(
+                "expected broadcast, fanout or push-pull, got {other}"
+            )),
+        }
+    }
+}
+
+/// Allows up to `per_sec` events in every one second window.
+pub struct RateLimit {
+    per_sec: u32,
+    window_start: Option<Instant>,
+    used: u32,
+}
+
+impl RateLimit {
+    pub fn new(per_sec: u32) -> Self {
+        Self {
+            per_sec,
+            window_start: None,
+            used: 0,
+        }
+    }
+
+    pub fn try_acquire(&mut self, now: Instant) -> bool {
+        if self
+            .window_start
+            .is_none_or(|start| now.duration_since(start) >= Duration::from_secs(1))
+        {
+            self.window_start = Some(now);
+            self.used = 0;
+        }
+        if self.used < self.per_sec {
+            self.used += 1;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// JSON objects are written with sorted keys, so equal states of the CRDTs
+/// here serialize the same way as long as their sequences are sorted too.
+pub fn digest<S: Serialize>(state: &S) -> StateDigest {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_value(state)
+        .unwrap()
+        .to_string()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod replication_tests {
+    use std::collections::HashMap;
+
+    use tokio::time::{Duration, Instant};
+
+    use super::{digest, RateLimit};
+
+    #[test]
+    fn limits_per_second() {
+        let mut limit = RateLimit::new(2);
+        let start = Instant::now();
+        assert!(limit.try_acquire(start));
+        assert!(limit.try_acquire(start + Duration::from_millis(10)));
+        assert!(!limit.try_acquire(start + Duration::from_millis(999)));
+        assert!(limit.try_acquire(start + Duration::from_secs(1)));
+        assert!(!RateLimit::new(0).try_acquire(start));
+    }
+
+    #[test]
+    fn digest_ignores_map_order() {
+        let a: HashMap<_, _> = (0..50).map(|i| (format!("n{i}"), i)).collect();
+        let b: HashMap<_, _> = (0..50).rev().map(|i| (format!("n{i}"), i)).collect();
+        assert_eq!(digest(&a), digest(&b));
+        assert_ne!(digest(&a), digest(&HashMap::from([("n0", 1)])));
+    }
+}
@@ -0,0 +1,360 @@
+//! Several [`CrdtNode`]s on one thread over a simulated network. Messages are
+//! delivered in the order they were sent unless a partition or random loss
+//! drops them, and nodes only replicate on [`Simulation::tick`], so a seed
+//! always replays the same run.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use futures::future::BoxFuture;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::Value;
+use tokio::time::Duration;
+
+use super::{replication::Strategy, Config, Crdt, CrdtNode, NodeConfig};
+use crate::io::{self, transport::Transport};
+use crate::protocol::{gen_next_msg_id, Body, Message, NodeId, RawMessage};
+
+const CLIENT: &str = "c1";
+
+/// Holds everything the nodes send until the simulation delivers it.
+#[derive(Default)]
+struct SimNetwork {
+    queue: Mutex<VecDeque<String>>,
+}
+
+impl SimNetwork {
+    fn pop(&self) -> Option<String> {
+        self.queue.lock().unwrap().pop_front()
+    }
+}
+
+impl Transport for SimNetwork {
+    fn send(&self, line: String) {
+        self.queue.lock().unwrap().push_back(line);
+    }
+
+    /// Nodes never wait for messages, the simulation hands them over.
+    fn recv(&self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async { None })
+    }
+
+    fn recv_blocking(&self) -> Option<String> {
+        None
+    }
+}
+
+pub struct Simulation<C: Crdt> {
+    node_ids: Vec<NodeId>,
+    nodes: Vec<CrdtNode<C>>,
+    network: Arc<SimNetwork>,
+    rng: StdRng,
+    /// Probability of dropping a message between nodes.
+    loss: f64,
+    /// Partition group of every node, messages only reach the same group.
+    groups: Vec<usize>,
+}
+
+impl<C: Crdt + Send + 'static> Simulation<C> {
+    /// Nodes `n0` to `n<count - 1>` replicating to every other node, sending
+    /// through the transport of the current thread.
+    pub fn new(count: usize, seed: u64, new_crdt: impl FnMut() -> C) -> Self {
+        Self::with_strategy(count, seed, Strategy::Broadcast, 0, new_crdt)
+    }
+
+    pub fn with_strategy(
+        count: usize,
+        seed: u64,
+        strategy: Strategy,
+        fanout: usize,
+        mut new_crdt: impl FnMut() -> C,
+    ) -> Self {
+        let network = Arc::new(SimNetwork::default());
+        io::set_transport(network.clone());
+        let node_ids: Vec<NodeId> = (0..count).map(|i| format!("n{i}")).collect();
+        let config = Config {
+            replication_interval: Duration::ZERO,
+            strategy,
+            fanout,
+            eager_rounds_per_sec: 0,
+            max_deltas: 1000,
+        };
+        let nodes = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, node_id)| {
+                let node_config = NodeConfig {
+                    node_id: node_id.clone(),
+                    node_ids: node_ids.clone(),
+                };
+                let rng = StdRng::seed_from_u64(seed + i as u64 + 1);
+                CrdtNode::new(new_crdt(), &config, node_config, rng)
+            })
+            .collect();
+        Self {
+            node_ids,
+            nodes,
+            network,
+            rng: StdRng::seed_from_u64(seed),
+            loss: 0.0,
+            groups: vec![0; count],
+        }
+    }
+
+    pub fn set_loss(&mut self, loss: f64) {
+        self.loss = loss;
+    }
+
+    /// Nodes left out of the given groups form one more group.
+    pub fn partition(&mut self, groups: &[&[usize]]) {
+        self.groups = vec![groups.len(); self.nodes.len()];
+        for (group, nodes) in groups.iter().enumerate() {
+            for &node in *nodes {
+                self.groups[node] = group;
+            }
+        }
+    }
+
+    pub fn heal(&mut self) {
+        self.groups.fill(0);
+    }
+
+    /// Sends a client request to the node and returns the reply. Clients
+    /// reach every node, partitions and loss only apply between nodes.
+    pub fn request(&mut self, node: usize, body: C::Body) -> Option<C::Body> {
+        let msg = Message {
+            src: CLIENT.to_owned(),
+            dest: self.node_ids[node].clone(),
+            body: Body {
+                msg_id: Some(gen_next_msg_id()),
+                in_reply_to: None,
+                data: body,
+            },
+        };
+        self.nodes[node].handle_msg(msg.to_raw().unwrap());
+        let mut queue = self.network.queue.lock().unwrap();
+        let idx = queue.iter().position(|line| {
+            let resp: RawMessage = serde_json::from_str(line).unwrap();
+            resp.dest == CLIENT && resp.body.in_reply_to == msg.body.msg_id
+        })?;
+        let resp: Message<C::Body> = serde_json::from_str(&queue.remove(idx).unwrap()).unwrap();
+        Some(resp.body.data)
+    }
+
+    /// Every node replicates once, then messages are delivered until there
+    /// are none left, including the acks.
+    pub fn tick(&mut self) {
+        for node in &self.nodes {
+            node.tick();
+        }
+        while let Some(line) = self.network.pop() {
+            let msg: RawMessage = serde_json::from_str(&line).unwrap();
+            let (Some(src), Some(dest)) = (self.index(&msg.src), self.index(&msg.dest)) else {
+                log::warn!("Dropping message outside the simulation {line}");
+                continue;
+            };
+            if self.groups[src] != self.groups[dest] || self.rng.gen_bool(self.loss) {
+                continue;
+            }
+            self.nodes[dest].handle_msg(msg);
+        }
+    }
+
+    /// Read values of every node. Arrays are sorted, sets like the G-Set's
+    /// don't keep an order.
+    pub fn read_all(&mut self, read: impl Fn() -> C::Body) -> Vec<Value> {
+        (0..self.nodes.len())
+            .map(|node| {
+                let resp = serde_json::to_value(self.request(node, read())).unwrap();
+                let mut value = resp["value"].clone();
+                if let Value::Array(ref mut values) = value {
+                    values.sort_by_key(Value::to_string);
+                }
+                value
+            })
+            .collect()
+    }
+
+    /// The value every node reads, `None` while they disagree.
+    pub fn converged(&mut self, read: impl Fn() -> C::Body) -> Option<Value> {
+        let values = self.read_all(read);
+        values
+            .windows(2)
+            .all(|pair| pair[0] == pair[1])
+            .then(|| values[0].clone())
+    }
+
+    fn index(&self, node_id: &str) -> Option<usize> {
+        self.node_ids.iter().position(|id| id == node_id)
+    }
+}
+
+#[cfg(test)]
+mod sim_tests {
+    use std::collections::BTreeSet;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use serde_json::{json, Value};
+
+    use super::Simulation;
+    use crate::protocol::crdts::{counter::CounterBodyData, or_set::OrSetBodyData};
+    use crate::protocol::ErrorCode;
+    use crate::workloads::crdts::{
+        bounded_counter::BoundedCounter, g_counter::GCounter, or_set::OrSet, pn_counter::PnCounter,
+        replication::Strategy, Crdt,
+    };
+
+    const NODES: usize = 5;
+
+    /// Random requests to random nodes while `n0`, `n1` are cut off from
+    /// the rest and messages get lost, then heals the partition and ticks
+    /// until every node reads the same value.
+    fn converges_after_heal<C: Crdt + Send + 'static>(
+        sim: &mut Simulation<C>,
+        rng: &mut StdRng,
+        mut random_op: impl FnMut(&mut StdRng) -> C::Body,
+        read: impl Fn() -> C::Body + Copy,
+    ) -> Value {
+        sim.partition(&[&[0, 1]]);
+        sim.set_loss(0.3);
+        for _ in 0..10 {
+            for _ in 0..5 {
+                let node = rng.gen_range(0..NODES);
+                assert!(sim.request(node, random_op(rng)).is_some());
+            }
+            sim.tick();
+        }
+        sim.heal();
+        for _ in 0..50 {
+            sim.tick();
+            if let Some(value) = sim.converged(read) {
+                return value;
+            }
+        }
+        panic!("No convergence after healing: {:?}", sim.read_all(read));
+    }
+
+    #[test]
+    fn g_counter_heals_partition() {
+        for seed in 0..5 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut sim = Simulation::new(NODES, seed, GCounter::default);
+            let mut total = 0;
+            let value = converges_after_heal(
+                &mut sim,
+                &mut rng,
+                |rng| {
+                    let delta = rng.gen_range(1..10);
+                    total += delta;
+                    CounterBodyData::Add { delta }
+                },
+                || CounterBodyData::Read,
+            );
+            assert_eq!(value, json!(total));
+        }
+    }
+
+    #[test]
+    fn partitioned_nodes_diverge() {
+        let mut sim = Simulation::new(NODES, 0, PnCounter::default);
+        sim.partition(&[&[0, 1]]);
+        sim.request(0, CounterBodyData::Add { delta: -3 });
+        sim.request(4, CounterBodyData::Add { delta: 5 });
+        sim.tick();
+        let values = sim.read_all(|| CounterBodyData::Read);
+        assert_eq!(
+            values,
+            vec![json!(-3), json!(-3), json!(5), json!(5), json!(5)]
+        );
+        sim.heal();
+        sim.tick();
+        assert_eq!(sim.converged(|| CounterBodyData::Read), Some(json!(2)));
+    }
+
+    #[test]
+    fn or_set_heals_partition() {
+        for seed in 0..5 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut sim = Simulation::new(NODES, seed, OrSet::default);
+            let mut added = BTreeSet::new();
+            let value = converges_after_heal(
+                &mut sim,
+                &mut rng,
+                |rng| {
+                    let element = rng.gen_range(0..10);
+                    if rng.gen_range(0..3) == 0 {
+                        OrSetBodyData::Remove { element }
+                    } else {
+                        added.insert(element);
+                        OrSetBodyData::Add { element }
+                    }
+                },
+                || OrSetBodyData::Read,
+            );
+            let elements: BTreeSet<i32> = serde_json::from_value(value).unwrap();
+            assert!(elements.is_subset(&added));
+        }
+    }
+
+    #[test]
+    fn fanout_and_push_pull_heal_partition() {
+        for strategy in [Strategy::Fanout, Strategy::PushPull] {
+            for seed in 0..5 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut sim =
+                    Simulation::with_strategy(NODES, seed, strategy, 2, GCounter::default);
+                let mut total = 0;
+                let value = converges_after_heal(
+                    &mut sim,
+                    &mut rng,
+                    |rng| {
+                        let delta = rng.gen_range(1..10);
+                        total += delta;
+                        CounterBodyData::Add { delta }
+                    },
+                    || CounterBodyData::Read,
+                );
+                assert_eq!(value, json!(total), "{strategy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn bounded_counter_stays_non_negative() {
+        for seed in 0..5 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut sim = Simulation::new(NODES, seed, BoundedCounter::default);
+            let value = converges_after_heal(
+                &mut sim,
+                &mut rng,
+                |rng| CounterBodyData::Add {
+                    delta: rng.gen_range(-8..6),
+                },
+                || CounterBodyData::Read,
+            );
+            let value = value.as_i64().unwrap();
+            assert!(value >= 0);
+
+            // Rights move to the node asking for them once it can reach the others.
+            if value == 0 {
+                continue;
+            }
+            sim.set_loss(0.0);
+            let mut attempts = 0;
+            loop {
+                match sim.request(0, CounterBodyData::Add { delta: -value }) {
+                    Some(CounterBodyData::AddOk) => break,
+                    Some(CounterBodyData::Error(err)) => {
+                        assert_eq!(err.code, ErrorCode::TemporarilyUnavailable)
+                    }
+                    other => panic!("Unexpected response {other:?}"),
+                }
+                attempts += 1;
+                assert!(attempts < 10, "Rights never arrived, seed {seed}");
+                sim.tick();
+            }
+            sim.tick();
+            assert_eq!(sim.converged(|| CounterBodyData::Read), Some(json!(0)));
+        }
+    }
+}
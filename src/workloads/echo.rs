@@ -1,7 +1,34 @@
-use crate::{protocol::echo::*, io::{blocking::receive_msg, send_msg}};
+use super::Workload;
+use crate::{
+    cli::{CliError, Options},
+    io::{blocking::receive_msg, send_msg},
+    logging::{self, MsgSpan},
+    protocol::echo::*,
+};
+
+pub struct Echo;
+
+impl Workload for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn description(&self) -> &'static str {
+        "Echoes every request back"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("echo")
+    }
+
+    fn run(&self, _options: &Options) -> Result<(), CliError> {
+        run();
+        Ok(())
+    }
+}
 
 pub fn run() {
-    eprintln!("Running echo workload");
+    log::info!("Running echo workload");
     init();
     loop {
         handle_echo();
@@ -11,7 +38,8 @@ pub fn run() {
 fn init() {
     let msg: Message = receive_msg();
     if let BodyData::Init(ref init) = msg.body.data {
-        eprintln!("Init node {}", init.node_id);
+        logging::set_node_id(&init.node_id);
+        log::info!("Init node {}", init.node_id);
         let resp_msg = msg.create_response(BodyData::InitOk);
         send_msg(&resp_msg);
     } else {
@@ -21,12 +49,14 @@ fn init() {
 
 fn handle_echo() {
     let msg: Message = receive_msg();
-    if let BodyData::Echo(ref echo_data) = msg.body.data {
-        let resp_msg = msg.create_response(BodyData::EchoOk(EchoData {
-            echo: echo_data.echo.clone(),
-        }));
-        send_msg(&resp_msg);
-    } else {
-        panic!("Expected echo msg");
-    }
+    MsgSpan::of(&msg).sync_scope(|| {
+        if let BodyData::Echo(ref echo_data) = msg.body.data {
+            let resp_msg = msg.create_response(BodyData::EchoOk(EchoData {
+                echo: echo_data.echo.clone(),
+            }));
+            send_msg(&resp_msg);
+        } else {
+            panic!("Expected echo msg");
+        }
+    })
 }
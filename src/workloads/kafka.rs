@@ -0,0 +1,388 @@
+use futures::future::try_join_all;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::time::Duration;
+
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::io::kv_client::{handle_storage_resp, KvClient, KvError};
+use crate::io::{non_blocking::receive_msg, send_msg, sync_resp::log_metrics};
+use crate::logging::{self, MsgSpan};
+use crate::protocol::{kafka::*, kv::LIN_KV_SERVICE, ErrorData, NodeId};
+use crate::workloads::Workload;
+
+pub static OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "timeout-ms",
+        description: "Timeout of a single lin-kv request",
+        default: "1000",
+    },
+    OptionSpec {
+        name: "poll-limit",
+        description: "Maximum number of messages returned for every key of a poll",
+        default: "20",
+    },
+];
+
+pub struct Config {
+    timeout: Duration,
+    poll_limit: u64,
+}
+
+impl Config {
+    pub fn from_options(options: &Options) -> Result<Self, CliError> {
+        Ok(Self {
+            timeout: options.millis("timeout-ms")?,
+            poll_limit: options.get("poll-limit")?,
+        })
+    }
+}
+
+pub struct Kafka;
+
+impl Workload for Kafka {
+    fn name(&self) -> &'static str {
+        "kafka"
+    }
+
+    fn description(&self) -> &'static str {
+        "Append only logs kept in lin-kv, every node claims offsets with cas"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("kafka")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(main(config));
+}
+
+async fn main(config: Config) {
+    let node_id = init_node().await;
+    let handler = Arc::new(Handler::new(node_id, &config));
+    let metrics_handler = handler.clone();
+    log_metrics(LIN_KV_SERVICE, move || metrics_handler.kv.metrics());
+    loop {
+        let msg: Message = receive_msg().await;
+        let handler = handler.clone();
+        let span = MsgSpan::of(&msg);
+        tokio::spawn(span.scope(async move { handler.handle_msg(msg).await }));
+    }
+}
+
+async fn init_node() -> NodeId {
+    let msg: Message = receive_msg().await;
+    match msg.body.data {
+        BodyData::Init(ref data) => {
+            logging::set_node_id(&data.node_id);
+            log::info!("Received init msg: {data:?}");
+            send_msg(&msg.create_response(BodyData::InitOk));
+            data.node_id.clone()
+        }
+        _ => panic!("Expected init msg, got {:?}", msg),
+    }
+}
+
+/// Every message is stored under its own lin-kv key. Wrapped so that a null
+/// message can't be mistaken for a free offset.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    msg: Value,
+}
+
+fn entry_key(key: &str, offset: Offset) -> (&'static str, &str, Offset) {
+    ("msg", key, offset)
+}
+
+fn committed_key(key: &str) -> (&'static str, &str) {
+    ("committed", key)
+}
+
+struct Handler {
+    kv: KvClient,
+    poll_limit: u64,
+    /// Offsets below these are known to be taken, so offsets are claimed and
+    /// read without gaps.
+    next_offsets: Mutex<HashMap<LogKey, Offset>>,
+}
+
+impl Handler {
+    fn new(node_id: NodeId, config: &Config) -> Self {
+        Self {
+            kv: KvClient::new(node_id, LIN_KV_SERVICE).with_timeout(config.timeout),
+            poll_limit: config.poll_limit,
+            next_offsets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn handle_msg(&self, msg: Message) {
+        let res = match msg.body.data {
+            BodyData::Send { ref key, ref msg } => self
+                .send(key, msg)
+                .await
+                .map(|offset| BodyData::SendOk { offset }),
+            BodyData::Poll { ref offsets } => self
+                .poll(offsets)
+                .await
+                .map(|msgs| BodyData::PollOk { msgs }),
+            BodyData::CommitOffsets { ref offsets } => self
+                .commit_offsets(offsets)
+                .await
+                .map(|()| BodyData::CommitOffsetsOk),
+            BodyData::ListCommittedOffsets { ref keys } => self
+                .list_committed_offsets(keys)
+                .await
+                .map(|offsets| BodyData::ListCommittedOffsetsOk { offsets }),
+            BodyData::CasOk | BodyData::ReadOk { .. } | BodyData::WriteOk | BodyData::Error(..) => {
+                handle_storage_resp(&[&self.kv], msg).await;
+                return;
+            }
+            _ => {
+                log::warn!("Ignoring unexpected message {:?}", msg);
+                return;
+            }
+        };
+        send_msg(&msg.create_response(res.unwrap_or_else(BodyData::Error)));
+    }
+
+    /// Claims the first free offset by creating its entry, `cas` from null only
+    /// succeeds if the key doesn't exist yet.
+    async fn send(&self, key: &str, msg: &Value) -> Result<Offset, ErrorData> {
+        let entry = Some(Entry { msg: msg.clone() });
+        let mut offset = self.next_offset(key);
+        loop {
+            match self
+                .kv
+                .cas(&entry_key(key, offset), &None, &entry, true)
+                .await
+            {
+                Ok(()) => {
+                    self.advance(key, offset + 1);
+                    return Ok(offset);
+                }
+                Err(KvError::PreconditionFailed(_)) => offset += 1,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    async fn poll(
+        &self,
+        offsets: &HashMap<LogKey, Offset>,
+    ) -> Result<HashMap<LogKey, Vec<(Offset, Value)>>, ErrorData> {
+        let logs = try_join_all(offsets.iter().map(|(key, &from)| async move {
+            Ok::<_, ErrorData>((key.clone(), self.read_log(key, from).await?))
+        }))
+        .await?;
+        Ok(logs
+            .into_iter()
+            .filter(|(_, msgs)| !msgs.is_empty())
+            .collect())
+    }
+
+    /// Up to `poll_limit` messages from the offset on, the known ones are read
+    /// at once and the rest one by one until the first free offset.
+    async fn read_log(&self, key: &str, from: Offset) -> Result<Vec<(Offset, Value)>, ErrorData> {
+        let end = from + self.poll_limit;
+        let known = self.next_offset(key).clamp(from, end);
+        let entries =
+            try_join_all((from..known).map(|offset| self.read_entry(key, offset))).await?;
+        let mut msgs: Vec<_> = (from..)
+            .zip(entries)
+            .map_while(|(offset, entry)| entry.map(|entry| (offset, entry.msg)))
+            .collect();
+        let mut offset = from + msgs.len() as Offset;
+        while offset < end {
+            match self.read_entry(key, offset).await? {
+                Some(entry) => msgs.push((offset, entry.msg)),
+                None => break,
+            }
+            offset += 1;
+        }
+        self.advance(key, offset);
+        Ok(msgs)
+    }
+
+    async fn read_entry(&self, key: &str, offset: Offset) -> Result<Option<Entry>, ErrorData> {
+        match self.kv.read(&entry_key(key, offset)).await {
+            Ok(entry) => Ok(Some(entry)),
+            Err(KvError::KeyDoesNotExist) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn commit_offsets(&self, offsets: &HashMap<LogKey, Offset>) -> Result<(), ErrorData> {
+        try_join_all(
+            offsets
+                .iter()
+                .map(|(key, &offset)| self.commit_offset(key, offset)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Committed offsets never go back, a lower commit is ignored.
+    async fn commit_offset(&self, key: &str, offset: Offset) -> Result<(), ErrorData> {
+        loop {
+            let committed = self.read_committed(key).await?;
+            if committed.is_some_and(|committed| committed >= offset) {
+                return Ok(());
+            }
+            match self
+                .kv
+                .cas(&committed_key(key), &committed, &Some(offset), true)
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(KvError::PreconditionFailed(_)) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    async fn list_committed_offsets(
+        &self,
+        keys: &[LogKey],
+    ) -> Result<HashMap<LogKey, Offset>, ErrorData> {
+        let committed = try_join_all(keys.iter().map(|key| async move {
+            Ok::<_, ErrorData>((key.clone(), self.read_committed(key).await?))
+        }))
+        .await?;
+        Ok(committed
+            .into_iter()
+            .filter_map(|(key, offset)| Some((key, offset?)))
+            .collect())
+    }
+
+    async fn read_committed(&self, key: &str) -> Result<Option<Offset>, ErrorData> {
+        match self.kv.read(&committed_key(key)).await {
+            Ok(offset) => Ok(Some(offset)),
+            Err(KvError::KeyDoesNotExist) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn next_offset(&self, key: &str) -> Offset {
+        let next_offsets = self.next_offsets.lock().unwrap();
+        next_offsets.get(key).copied().unwrap_or_default()
+    }
+
+    fn advance(&self, key: &str, next: Offset) {
+        let mut next_offsets = self.next_offsets.lock().unwrap();
+        let known = next_offsets.entry(key.to_owned()).or_default();
+        *known = (*known).max(next);
+    }
+}
+
+#[cfg(test)]
+mod kafka_tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use serde_json::{json, Value};
+    use tokio::time::Duration;
+
+    use super::{Config, Handler};
+    use crate::io::transport::{ChannelNetwork, ChannelTransport, Transport};
+    use crate::protocol::{kv::*, ErrorCode, ErrorData};
+
+    /// In-memory lin-kv shared by the nodes of the test.
+    async fn serve_lin_kv(service: ChannelTransport, nodes: &[(&str, &Handler)]) {
+        let mut store: HashMap<String, Value> = HashMap::new();
+        while let Some(req) = service.recv().await {
+            let req: Message = serde_json::from_str(&req).unwrap();
+            let resp = match req.body.data {
+                BodyData::Read { ref key } => match store.get(&key.to_string()) {
+                    Some(value) => BodyData::ReadOk {
+                        value: value.clone(),
+                    },
+                    None => BodyData::Error(ErrorData::new(
+                        "missing".to_owned(),
+                        ErrorCode::KeyDoesNotExist,
+                    )),
+                },
+                BodyData::Cas {
+                    ref key,
+                    ref from,
+                    ref to,
+                    create_if_not_exists,
+                } => match store.get(&key.to_string()) {
+                    Some(current) if current != from => BodyData::Error(ErrorData::new(
+                        "mismatch".to_owned(),
+                        ErrorCode::PreconditionFailed,
+                    )),
+                    None if !create_if_not_exists => BodyData::Error(ErrorData::new(
+                        "missing".to_owned(),
+                        ErrorCode::KeyDoesNotExist,
+                    )),
+                    _ => {
+                        store.insert(key.to_string(), to.clone());
+                        BodyData::CasOk
+                    }
+                },
+                ref other => panic!("Unexpected request {other:?}"),
+            };
+            let (_, node) = nodes.iter().find(|(id, _)| *id == req.src).unwrap();
+            node.kv.handle(req.create_response(resp)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn offsets_are_shared_by_nodes() {
+        let network = ChannelNetwork::new();
+        crate::io::set_transport(Arc::new(network.connect("n1")));
+        let config = Config {
+            timeout: Duration::from_secs(1),
+            poll_limit: 2,
+        };
+        let n1 = Handler::new("n1".to_owned(), &config);
+        let n2 = Handler::new("n2".to_owned(), &config);
+        let scenario = async {
+            assert_eq!(n1.send("k", &json!(1)).await.unwrap(), 0);
+            assert_eq!(n2.send("k", &json!(2)).await.unwrap(), 1);
+            assert_eq!(n1.send("k", &json!(null)).await.unwrap(), 2);
+            assert_eq!(n2.send("j", &json!(4)).await.unwrap(), 0);
+
+            let offsets = HashMap::from([("k".to_owned(), 1), ("j".to_owned(), 1)]);
+            let msgs = n2.poll(&offsets).await.unwrap();
+            assert_eq!(
+                msgs,
+                HashMap::from([("k".to_owned(), vec![(1, json!(2)), (2, json!(null))])])
+            );
+            let msgs = n1
+                .poll(&HashMap::from([("k".to_owned(), 0)]))
+                .await
+                .unwrap();
+            assert_eq!(msgs["k"], vec![(0, json!(1)), (1, json!(2))]);
+
+            n1.commit_offsets(&HashMap::from([("k".to_owned(), 2)]))
+                .await
+                .unwrap();
+            n2.commit_offsets(&HashMap::from([("k".to_owned(), 1)]))
+                .await
+                .unwrap();
+            let keys = ["k".to_owned(), "j".to_owned()];
+            let committed = n2.list_committed_offsets(&keys).await.unwrap();
+            assert_eq!(committed, HashMap::from([("k".to_owned(), 2)]));
+        };
+        let nodes = [("n1", &n1), ("n2", &n2)];
+        tokio::select! {
+            _ = serve_lin_kv(network.connect(LIN_KV_SERVICE), &nodes) => unreachable!(),
+            _ = scenario => {}
+        }
+    }
+}
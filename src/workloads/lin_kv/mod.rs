@@ -1,5 +1,6 @@
 use crate::{
     io::{non_blocking::receive_msg, send_msg},
+    logging,
     protocol::{
         link_kv::{BodyData, Message},
         NodeId,
@@ -17,7 +18,8 @@ pub async fn init_node() -> NodeConfig {
     let msg: Message = receive_msg().await;
     match msg.body.data {
         BodyData::Init(ref data) => {
-            eprintln!("Received init msg: {data:?}");
+            logging::set_node_id(&data.node_id);
+            log::info!("Received init msg: {data:?}");
             send_msg(&msg.create_response(BodyData::InitOk));
             NodeConfig {
                 node_id: data.node_id.clone(),
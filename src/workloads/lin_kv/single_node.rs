@@ -1,6 +1,30 @@
 use super::{init_node, local_state::KvStateMachine};
+use crate::cli::{CliError, Options};
 use crate::io::{non_blocking::receive_msg, send_msg};
-use crate::protocol::{link_kv::*, ErrorData};
+use crate::logging::MsgSpan;
+use crate::protocol::{link_kv::*, ErrorCode, ErrorData};
+use crate::workloads::Workload;
+
+pub struct SingleNode;
+
+impl Workload for SingleNode {
+    fn name(&self) -> &'static str {
+        "lin-kv-single-node"
+    }
+
+    fn description(&self) -> &'static str {
+        "Linearizable key-value store on a single node"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("lin-kv")
+    }
+
+    fn run(&self, _options: &Options) -> Result<(), CliError> {
+        run();
+        Ok(())
+    }
+}
 
 pub fn run() {
     tokio::runtime::Builder::new_current_thread()
@@ -15,29 +39,31 @@ async fn main() {
     let mut state = KvStateMachine::new();
     loop {
         let msg: Message = receive_msg().await;
-        let resp_body = handle_request(&mut state, &msg.body.data);
-        let resp = msg.create_response(resp_body);
-        send_msg(&resp);
+        MsgSpan::of(&msg).sync_scope(|| {
+            let resp_body =
+                handle_request(&mut state, &msg.body.data).unwrap_or_else(BodyData::Error);
+            if let BodyData::Error(ref err) = resp_body {
+                log::info!("Request failed: {err}");
+            }
+            send_msg(&msg.create_response(resp_body));
+        });
     }
 }
 
-fn handle_request(state: &mut KvStateMachine, data: &BodyData) -> BodyData {
+fn handle_request(state: &mut KvStateMachine, data: &BodyData) -> Result<BodyData, ErrorData> {
     match data {
-        BodyData::Read(data) => {
-            handle_error(state.read(data).map(|read_ok| BodyData::ReadOk(read_ok)))
-        }
+        BodyData::Read(data) => Ok(BodyData::ReadOk(state.read(data)?)),
         BodyData::Write(data) => {
             state.write(data);
-            BodyData::WriteOk
+            Ok(BodyData::WriteOk)
         }
-        BodyData::Cas(data) => handle_error(state.cas(data).map(|_| BodyData::CasOk)),
-        other => panic!("Unknown request body: {:?}", other),
-    }
-}
-
-fn handle_error(res: Result<BodyData, ErrorData>) -> BodyData {
-    match res {
-        Ok(data) => data,
-        Err(err) => BodyData::Error(err),
+        BodyData::Cas(data) => {
+            state.cas(data)?;
+            Ok(BodyData::CasOk)
+        }
+        other => Err(ErrorData::new(
+            format!("Unsupported request: {other:?}"),
+            ErrorCode::NotSupported,
+        )),
     }
 }
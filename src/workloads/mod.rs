@@ -1,5 +1,61 @@
+use crate::cli::{CliError, OptionSpec, Options};
+
 pub mod broadcast;
 pub mod crdts;
 pub mod echo;
+pub mod kafka;
 pub mod lin_kv;
 pub mod txn_list_append;
+pub mod txn_rw_register;
+pub mod unique_ids;
+
+/// A node implementation, selected by its name on the command line.
+pub trait Workload: Sync {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    /// The Maelstrom workload (`maelstrom test -w`) the node is built for,
+    /// several variants might implement the same one. None if Maelstrom has
+    /// no workload checking it.
+    fn maelstrom_workload(&self) -> Option<&'static str>;
+    fn options(&self) -> &'static [OptionSpec] {
+        &[]
+    }
+    fn run(&self, options: &Options) -> Result<(), CliError>;
+}
+
+pub static WORKLOADS: &[&dyn Workload] = &[
+    &echo::Echo,
+    &unique_ids::UniqueIds,
+    &broadcast::Broadcast,
+    &crdts::g_set::GSetWorkload,
+    &crdts::g_counter::GCounterWorkload,
+    &crdts::pn_counter::PnCounterWorkload,
+    &crdts::bounded_counter::BoundedCounterWorkload,
+    &crdts::or_set::OrSetWorkload,
+    &crdts::registers::LwwRegisterWorkload,
+    &crdts::registers::MvRegisterWorkload,
+    &crdts::map::CrdtMapWorkload,
+    &txn_list_append::single_node::SingleNode,
+    &txn_list_append::shared_state::SharedState,
+    &txn_list_append::splitted_state::SplittedState,
+    &txn_rw_register::TxnRwRegister,
+    &lin_kv::single_node::SingleNode,
+    &kafka::Kafka,
+];
+
+pub fn find(name: &str) -> Option<&'static dyn Workload> {
+    WORKLOADS.iter().copied().find(|w| w.name() == name)
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use std::collections::HashSet;
+
+    use super::WORKLOADS;
+
+    #[test]
+    fn unique_names() {
+        let names: HashSet<_> = WORKLOADS.iter().map(|w| w.name()).collect();
+        assert_eq!(names.len(), WORKLOADS.len());
+    }
+}
@@ -1,45 +1,101 @@
-use serde_json::{json, Value};
+use serde_json::Value;
 use std::sync::Arc;
 use tokio::time::Duration;
 
 use super::local_state::LocalState;
 use super::{init_node, NodeConfig};
-use crate::io::{non_blocking::receive_msg, send_msg};
-use crate::protocol::{txn_list_append::*, gen_next_msg_id, Body, ErrorCode, ErrorData};
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::io::kv_client::{handle_storage_resp, KvClient, KvError};
+use crate::io::{non_blocking::receive_msg, send_msg, sync_resp::log_metrics};
+use crate::logging::MsgSpan;
+use crate::protocol::{kv::LIN_KV_SERVICE, txn_list_append::*, ErrorCode, ErrorData};
+use crate::workloads::Workload;
 
-type SyncRespHandler = crate::io::sync_resp::SyncRespHandler<BodyData>;
+pub static OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "timeout-ms",
+        description: "Timeout of a single lin-kv request",
+        default: "1000",
+    },
+    OptionSpec {
+        name: "read-retries",
+        description: "Resends of a timed out lin-kv read",
+        default: "2",
+    },
+];
 
-pub fn run() {
+pub struct Config {
+    timeout: Duration,
+    read_retries: u32,
+}
+
+impl Config {
+    pub fn from_options(options: &Options) -> Result<Self, CliError> {
+        Ok(Self {
+            timeout: options.millis("timeout-ms")?,
+            read_retries: options.get("read-retries")?,
+        })
+    }
+}
+
+pub struct SharedState;
+
+impl Workload for SharedState {
+    fn name(&self) -> &'static str {
+        "txn-list-append-shared-state"
+    }
+
+    fn description(&self) -> &'static str {
+        "List append transactions over the whole state stored under one lin-kv key"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("txn-list-append")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .unwrap()
-        .block_on(main());
+        .block_on(main(config));
 }
 
-async fn main() {
-    let config = init_node().await;
-    let handler = Arc::new(Handler::new(config));
+async fn main(config: Config) {
+    let node_config = init_node().await;
+    let handler = Arc::new(Handler::new(node_config, &config));
+    let metrics_handler = handler.clone();
+    log_metrics(LIN_KV_SERVICE, move || metrics_handler.kv.metrics());
     loop {
         let msg: Message = receive_msg().await;
         let handler = handler.clone();
-        tokio::spawn(async move { handler.handle_msg(msg).await });
+        let span = MsgSpan::of(&msg);
+        tokio::spawn(span.scope(async move { handler.handle_msg(msg).await }));
     }
 }
 
 struct Handler {
-    sync_resp: SyncRespHandler,
-    config: NodeConfig,
+    kv: KvClient,
 }
 
-const TIMEOUT: Duration = Duration::from_secs(1);
 const ROOT_KEY: &str = "root";
 
 impl Handler {
-    fn new(config: NodeConfig) -> Self {
+    fn new(node_config: NodeConfig, config: &Config) -> Self {
         Self {
-            sync_resp: SyncRespHandler::new(),
-            config,
+            kv: KvClient::new(node_config.node_id, LIN_KV_SERVICE)
+                .with_timeout(config.timeout)
+                .with_retries(config.read_retries),
         }
     }
 
@@ -54,9 +110,9 @@ impl Handler {
                 send_msg(&msg.create_response(resp_body));
             }
             BodyData::CasOk | BodyData::ReadOk { value: _ } | BodyData::Error(_) => {
-                self.sync_resp.handle(msg).await;
+                handle_storage_resp(&[&self.kv], msg).await;
             }
-            _ => eprintln!("Ignoring unexpected message {:?}", msg),
+            _ => log::warn!("Ignoring unexpected message {:?}", msg),
         }
     }
 
@@ -73,72 +129,31 @@ impl Handler {
     }
 
     async fn update_state(&self, prev: Option<Value>, updated: Value) -> Result<(), ErrorData> {
-        let resp = self
-            .sync_resp
-            .send(
-                self.create_storage_request(BodyData::Cas {
-                    key: json!(ROOT_KEY),
-                    from: prev.unwrap_or(Value::Null),
-                    to: updated,
-                    create_if_not_exists: true,
-                }),
-                TIMEOUT,
-            )
+        let res = self
+            .kv
+            .cas(&ROOT_KEY, &prev.unwrap_or(Value::Null), &updated, true)
             .await;
-        match resp {
-            Some(msg) => match msg.body.data {
-                BodyData::CasOk => Ok(()),
-                BodyData::Error(ErrorData { text, code }) => {
-                    let data = match code {
-                        ErrorCode::PreconditionFailed => ErrorData::new(
-                            "Aborted due to concurrent transaction".to_owned(),
-                            ErrorCode::TxnConflict,
-                        ),
-                        _ => ErrorData::new(text, ErrorCode::Abort),
-                    };
-                    Err(data)
-                }
-                other => panic!("Expected cas_ok or error response, got {other:?}"),
-            },
-            None => Err(ErrorData::new(
-                "Timeout while saving the updated state".to_owned(),
+        res.map_err(|err| match err {
+            KvError::PreconditionFailed(_) => ErrorData::new(
+                "Aborted due to concurrent transaction".to_owned(),
+                ErrorCode::TxnConflict,
+            ),
+            KvError::Rpc(err) => ErrorData::new(
+                format!("Failed to save the updated state: {err}"),
                 ErrorCode::Crash,
-            )),
-        }
+            ),
+            other => ErrorData::new(other.to_string(), ErrorCode::Abort),
+        })
     }
 
     async fn read_state(&self) -> Result<Option<Value>, ErrorData> {
-        let resp = self
-            .sync_resp
-            .send(
-                self.create_storage_request(BodyData::Read {
-                    key: json!(ROOT_KEY),
-                }),
-                TIMEOUT,
-            )
-            .await;
-        match resp {
-            Some(msg) => match msg.body.data {
-                BodyData::ReadOk { value } => Ok(Some(value)),
-                BodyData::Error(ErrorData {
-                    text: _,
-                    code: ErrorCode::KeyDoesNotExist,
-                }) => Ok(None),
-                other => panic!("Expected read_ok response, got {other:?}"),
-            },
-            None => Err(ErrorData::new("Timeout reading state".to_owned(), ErrorCode::Abort)),
-        }
-    }
-
-    fn create_storage_request(&self, data: BodyData) -> Message {
-        Message {
-            src: self.config.node_id.clone(),
-            dest: LIN_KV_SERVICE.to_string(),
-            body: Body {
-                msg_id: Some(gen_next_msg_id()),
-                in_reply_to: None,
-                data,
-            },
+        match self.kv.read(&ROOT_KEY).await {
+            Ok(value) => Ok(Some(value)),
+            Err(KvError::KeyDoesNotExist) => Ok(None),
+            Err(err) => Err(ErrorData::new(
+                format!("Failed to read state: {err}"),
+                ErrorCode::Abort,
+            )),
         }
     }
 }
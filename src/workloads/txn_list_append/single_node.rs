@@ -1,6 +1,30 @@
 use super::{init_node, local_state::LocalState};
+use crate::cli::{CliError, Options};
 use crate::io::{non_blocking::receive_msg, send_msg};
+use crate::logging::MsgSpan;
 use crate::protocol::txn_list_append::*;
+use crate::workloads::Workload;
+
+pub struct SingleNode;
+
+impl Workload for SingleNode {
+    fn name(&self) -> &'static str {
+        "txn-list-append-single-node"
+    }
+
+    fn description(&self) -> &'static str {
+        "List append transactions applied to the local state of a single node"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("txn-list-append")
+    }
+
+    fn run(&self, _options: &Options) -> Result<(), CliError> {
+        run();
+        Ok(())
+    }
+}
 
 pub fn run() {
     tokio::runtime::Builder::new_current_thread()
@@ -15,12 +39,12 @@ async fn main() {
     let mut state = LocalState::default();
     loop {
         let msg: Message = receive_msg().await;
-        match msg.body.data {
+        MsgSpan::of(&msg).sync_scope(|| match msg.body.data {
             BodyData::Txn(ref txn_data) => {
                 let resp = msg.create_response(BodyData::TxnOk(state.apply_txn(txn_data)));
                 send_msg(&resp);
             }
-            _ => eprintln!("Ignoring unexpected message {:?}", msg),
-        }
+            _ => log::warn!("Ignoring unexpected message {:?}", msg),
+        });
     }
 }
@@ -1,44 +1,93 @@
 use futures::{future::try_join_all, FutureExt};
-use serde_json::{json, Value};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio::time::{sleep, Duration};
+use tokio::time::Duration;
 
 use super::local_state::LocalState;
-use super::{
-    create_storage_req, gen_next_storage_key, handle_cas_resp, handle_read_resp, handle_write_resp,
-    init_node, NodeConfig,
+use super::{gen_next_storage_key, init_node, NodeConfig};
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::io::kv_client::{handle_storage_resp, KvClient, KvError};
+use crate::io::{non_blocking::receive_msg, send_msg, sync_resp::log_metrics};
+use crate::logging::MsgSpan;
+use crate::protocol::{
+    kv::{LIN_KV_SERVICE, LWW_KV_SERVICE},
+    txn_list_append::*,
+    ErrorData,
 };
-use crate::io::{non_blocking::receive_msg, send_msg};
-use crate::protocol::{txn_list_append::*, ErrorData};
+use crate::workloads::Workload;
 
-type SyncRespHandler = crate::io::sync_resp::SyncRespHandler<BodyData>;
+pub static OPTIONS: &[OptionSpec] = &[OptionSpec {
+    name: "timeout-ms",
+    description: "Timeout of a single lin-kv or lww-kv request",
+    default: "1000",
+}];
 
-pub fn run() {
+pub struct Config {
+    timeout: Duration,
+}
+
+impl Config {
+    pub fn from_options(options: &Options) -> Result<Self, CliError> {
+        Ok(Self {
+            timeout: options.millis("timeout-ms")?,
+        })
+    }
+}
+
+pub struct SplittedState;
+
+impl Workload for SplittedState {
+    fn name(&self) -> &'static str {
+        "txn-list-append-splitted-state"
+    }
+
+    fn description(&self) -> &'static str {
+        "List append transactions over values in lww-kv with a lin-kv root pointer"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("txn-list-append")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
     tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .unwrap()
-        .block_on(main());
+        .block_on(main(config));
 }
 
-async fn main() {
-    let config = init_node().await;
-    let handler = Arc::new(Handler::new(config));
+async fn main(config: Config) {
+    let node_config = init_node().await;
+    let handler = Arc::new(Handler::new(node_config, &config));
+    let metrics_handler = handler.clone();
+    log_metrics(LIN_KV_SERVICE, move || metrics_handler.lin_kv.metrics());
+    let metrics_handler = handler.clone();
+    log_metrics(LWW_KV_SERVICE, move || metrics_handler.lww_kv.metrics());
     loop {
         let msg: Message = receive_msg().await;
         let handler = handler.clone();
-        tokio::spawn(async move { handler.handle_msg(msg).await });
+        let span = MsgSpan::of(&msg);
+        tokio::spawn(span.scope(async move { handler.handle_msg(msg).await }));
     }
 }
 
-const TIMEOUT: Duration = Duration::from_secs(1);
-const RETRY_DELAY: Duration = Duration::from_millis(10);
 const ROOT_KEY: &str = "root";
 type StorageKey = String;
 
 struct Handler {
-    sync_resp: SyncRespHandler,
+    lin_kv: KvClient,
+    lww_kv: KvClient,
     config: NodeConfig,
 }
 
@@ -48,10 +97,13 @@ struct RootState {
 }
 
 impl Handler {
-    fn new(config: NodeConfig) -> Self {
+    fn new(node_config: NodeConfig, config: &Config) -> Self {
         Self {
-            sync_resp: SyncRespHandler::new(),
-            config,
+            lin_kv: KvClient::new(node_config.node_id.clone(), LIN_KV_SERVICE)
+                .with_timeout(config.timeout),
+            lww_kv: KvClient::new(node_config.node_id.clone(), LWW_KV_SERVICE)
+                .with_timeout(config.timeout),
+            config: node_config,
         }
     }
 
@@ -66,9 +118,9 @@ impl Handler {
                 send_msg(&msg.create_response(resp_body));
             }
             BodyData::CasOk | BodyData::ReadOk { .. } | BodyData::WriteOk | BodyData::Error(..) => {
-                self.sync_resp.handle(msg).await;
+                handle_storage_resp(&[&self.lin_kv, &self.lww_kv], msg).await;
             }
-            _ => eprintln!("Ignoring unexpected message {:?}", msg),
+            _ => log::warn!("Ignoring unexpected message {:?}", msg),
         }
     }
 
@@ -113,15 +165,12 @@ impl Handler {
         self.write_lww_storage(state).await
     }
 
-    async fn read_root_key(&self) -> Result<Option<String>, ErrorData> {
-        let req_msg = self.create_lin_req(BodyData::Read {
-            key: json!(ROOT_KEY),
-        });
-        let maybe_value = handle_read_resp(self.sync_rpc(req_msg).await)?;
-        Ok(maybe_value.map(|value| match value {
-            Value::String(s) => s,
-            other => panic!("Unexpected root data type: {other:?}"),
-        }))
+    async fn read_root_key(&self) -> Result<Option<StorageKey>, ErrorData> {
+        match self.lin_kv.read(&ROOT_KEY).await {
+            Ok(key) => Ok(Some(key)),
+            Err(KvError::KeyDoesNotExist) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
     }
 
     async fn cas_root_key(
@@ -129,13 +178,15 @@ impl Handler {
         prev_key: Option<StorageKey>,
         next_key: StorageKey,
     ) -> Result<bool, ErrorData> {
-        let req_msg = self.create_lin_req(BodyData::Cas {
-            key: json!(ROOT_KEY),
-            from: serde_json::to_value(prev_key).unwrap(),
-            to: serde_json::to_value(next_key).unwrap(),
-            create_if_not_exists: true,
-        });
-        handle_cas_resp(self.sync_rpc(req_msg).await)
+        match self
+            .lin_kv
+            .cas(&ROOT_KEY, &prev_key, &Some(next_key), true)
+            .await
+        {
+            Ok(()) => Ok(true),
+            Err(KvError::PreconditionFailed(_)) => Ok(false),
+            Err(err) => Err(err.into()),
+        }
     }
 
     async fn read_txn_values(
@@ -196,11 +247,7 @@ impl Handler {
         value: T,
     ) -> Result<StorageKey, ErrorData> {
         let key = gen_next_storage_key(&self.config);
-        let req_msg = self.create_lww_req(BodyData::Write {
-            key: json!(&key),
-            value: serde_json::to_value(value).unwrap(),
-        });
-        handle_write_resp(self.sync_rpc(req_msg).await)?;
+        self.lww_kv.write(&key, &value).await?;
         Ok(key)
     }
 
@@ -208,26 +255,7 @@ impl Handler {
         &self,
         key: StorageKey,
     ) -> Result<T, ErrorData> {
-        loop {
-            let req_msg = self.create_lww_req(BodyData::Read { key: json!(key) });
-            match handle_read_resp(self.sync_rpc(req_msg).await)? {
-                Some(value) => return Ok(serde_json::from_value(value).unwrap()),
-                None => {
-                    sleep(RETRY_DELAY).await;
-                }
-            }
-        }
-    }
-
-    async fn sync_rpc(&self, msg: Message) -> Option<Message> {
-        self.sync_resp.send(msg, TIMEOUT).await
-    }
-
-    fn create_lww_req(&self, body: BodyData) -> Message {
-        create_storage_req(&self.config.node_id, LWW_KV_SERVICE, body)
-    }
-
-    fn create_lin_req(&self, body: BodyData) -> Message {
-        create_storage_req(&self.config.node_id, LIN_KV_SERVICE, body)
+        // Freshly written values might not have reached the lww-kv replica yet.
+        Ok(self.lww_kv.read_eventually(&key).await?)
     }
 }
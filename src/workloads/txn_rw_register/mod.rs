@@ -0,0 +1,212 @@
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::time::{Duration, Instant};
+
+use crate::cli::{CliError, OptionSpec, Options};
+use crate::io::{
+    non_blocking::{receive_msg, receive_msg_in_span},
+    send_msg,
+};
+use crate::logging;
+use crate::protocol::{gen_next_msg_id, txn_rw_register::*, Body, NodeId};
+use crate::workloads::Workload;
+use registers::Registers;
+use replication::{Batch, PendingWrites};
+
+mod registers;
+mod replication;
+
+pub static OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        name: "isolation",
+        description: "`read-uncommitted` applies every write as it runs, `read-committed` the final writes of a transaction at once",
+        default: "read-committed",
+    },
+    OptionSpec {
+        name: "retry-interval-ms",
+        description: "Delay before resending writes another node hasn't acknowledged",
+        default: "1000",
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Isolation {
+    ReadUncommitted,
+    ReadCommitted,
+}
+
+impl FromStr for Isolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-uncommitted" => Ok(Isolation::ReadUncommitted),
+            "read-committed" => Ok(Isolation::ReadCommitted),
+            other => Err(format!(
+                "expected read-uncommitted or read-committed, got {other}"
+            )),
+        }
+    }
+}
+
+pub struct Config {
+    isolation: Isolation,
+    retry_interval: Duration,
+}
+
+impl Config {
+    pub fn from_options(options: &Options) -> Result<Self, CliError> {
+        Ok(Self {
+            isolation: options.get("isolation")?,
+            retry_interval: options.millis("retry-interval-ms")?,
+        })
+    }
+}
+
+pub struct TxnRwRegister;
+
+impl Workload for TxnRwRegister {
+    fn name(&self) -> &'static str {
+        "txn-rw-register"
+    }
+
+    fn description(&self) -> &'static str {
+        "Totally available register transactions, writes reach other nodes in the background"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("txn-rw-register")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(Config::from_options(options)?);
+        Ok(())
+    }
+}
+
+pub fn run(config: Config) {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(main(config));
+}
+
+async fn main(config: Config) {
+    let (node_id, node_ids) = init_node().await;
+    let peers = node_ids.into_iter().filter(|id| *id != node_id);
+    let handler = Arc::new(Handler {
+        pending: Mutex::new(PendingWrites::new(peers, config.retry_interval)),
+        registers: Mutex::new(Registers::new(node_id.clone())),
+        node_id,
+        config,
+    });
+    let retry_handler = handler.clone();
+    tokio::spawn(async move { retry_handler.retransmit_forever().await });
+    loop {
+        let (msg, span): (Message, _) = receive_msg_in_span().await;
+        tokio::spawn(span.scope(handler.clone().handle_msg(msg)));
+    }
+}
+
+async fn init_node() -> (NodeId, Vec<NodeId>) {
+    let msg: Message = receive_msg().await;
+    match msg.body.data {
+        BodyData::Init(ref data) => {
+            logging::set_node_id(&data.node_id);
+            log::info!("Received init msg: {data:?}");
+            send_msg(&msg.create_response(BodyData::InitOk));
+            (data.node_id.clone(), data.node_ids.clone())
+        }
+        _ => panic!("Expected init msg, got {:?}", msg),
+    }
+}
+
+/// Every node serves transactions from its own registers without waiting for
+/// the others, writes are sent to every other node until it acknowledges them.
+struct Handler {
+    node_id: NodeId,
+    config: Config,
+    registers: Mutex<Registers>,
+    pending: Mutex<PendingWrites>,
+}
+
+/// Checks of the retransmission deadlines per retry interval.
+const RETRY_CHECKS_PER_INTERVAL: u32 = 4;
+
+impl Handler {
+    async fn handle_msg(self: Arc<Self>, msg: Message) {
+        match msg.body.data {
+            BodyData::Txn(ref txn_data) => {
+                let (res, writes) = self
+                    .registers
+                    .lock()
+                    .unwrap()
+                    .apply_txn(txn_data, self.config.isolation);
+                send_msg(&msg.create_response(BodyData::TxnOk(res)));
+                self.replicate(&writes);
+            }
+            BodyData::Replicate { ref writes } => {
+                self.registers.lock().unwrap().apply(writes);
+                send_msg(&msg.create_response(BodyData::ReplicateOk));
+            }
+            BodyData::ReplicateOk => {
+                if let Some(msg_id) = msg.body.in_reply_to {
+                    self.pending.lock().unwrap().acked(&msg.src, msg_id);
+                }
+            }
+            _ => log::warn!("Ignoring unexpected message {:?}", msg),
+        }
+    }
+
+    /// Sends the writes of a transaction in one message applied at once, those
+    /// replaced by newer pending writes go with the retransmissions instead.
+    fn replicate(&self, writes: &[VersionedWrite]) {
+        if writes.is_empty() {
+            return;
+        }
+        let batches = self
+            .pending
+            .lock()
+            .unwrap()
+            .add(writes, Instant::now(), gen_next_msg_id);
+        self.send_batches(batches);
+    }
+
+    /// Applying writes twice is harmless, so they are resent until acknowledged.
+    async fn retransmit_forever(&self) {
+        let mut retry_timer = tokio::time::interval(
+            (self.config.retry_interval / RETRY_CHECKS_PER_INTERVAL).max(Duration::from_millis(1)),
+        );
+        loop {
+            retry_timer.tick().await;
+            let batches = self
+                .pending
+                .lock()
+                .unwrap()
+                .retransmissions(Instant::now(), gen_next_msg_id);
+            for (dest, _, writes) in &batches {
+                log::info!("Resending {} unacknowledged writes to {dest}", writes.len());
+            }
+            self.send_batches(batches);
+        }
+    }
+
+    fn send_batches(&self, batches: Vec<Batch>) {
+        for (dest, msg_id, writes) in batches {
+            send_msg(&Message {
+                src: self.node_id.clone(),
+                dest,
+                body: Body {
+                    msg_id: Some(msg_id),
+                    in_reply_to: None,
+                    data: BodyData::Replicate { writes },
+                },
+            });
+        }
+    }
+}
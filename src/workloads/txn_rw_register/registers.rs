@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+
+use super::Isolation;
+use crate::protocol::{txn_rw_register::*, NodeId};
+
+/// Last writer wins registers, the write with the highest version is kept.
+pub struct Registers {
+    node_id: NodeId,
+    /// Highest version counter seen, local writes get a higher one.
+    clock: u64,
+    values: HashMap<KeyValue, (Version, RegisterValue)>,
+}
+
+impl Registers {
+    pub fn new(node_id: NodeId) -> Self {
+        Self {
+            node_id,
+            clock: 0,
+            values: HashMap::new(),
+        }
+    }
+
+    /// Returns the transaction result and the writes to replicate, the last one
+    /// of every key. All writes of a transaction share one version, so every
+    /// node orders two transactions the same way on all their keys.
+    pub fn apply_txn(
+        &mut self,
+        txn_data: &TxnData,
+        isolation: Isolation,
+    ) -> (TxnData, Vec<VersionedWrite>) {
+        match isolation {
+            Isolation::ReadUncommitted => self.apply_uncommitted(txn_data),
+            Isolation::ReadCommitted => self.apply_committed(txn_data),
+        }
+    }
+
+    /// Every write is applied right away, later ones of the same key replace
+    /// it although they have the same version.
+    fn apply_uncommitted(&mut self, txn_data: &TxnData) -> (TxnData, Vec<VersionedWrite>) {
+        let version = self.next_version();
+        let mut writes: Vec<VersionedWrite> = Vec::new();
+        let mut txn = Vec::with_capacity(txn_data.txn.len());
+        for func in &txn_data.txn {
+            match *func {
+                TxnFunc::Read { key, .. } => txn.push(TxnFunc::Read {
+                    key,
+                    value: self.get(key),
+                }),
+                TxnFunc::Write { key, value } => {
+                    self.values.insert(key, (version.clone(), value));
+                    writes.retain(|w| w.key != key);
+                    writes.push(VersionedWrite {
+                        key,
+                        value,
+                        version: version.clone(),
+                    });
+                    txn.push(func.clone());
+                }
+            }
+        }
+        (TxnData { txn }, writes)
+    }
+
+    /// Writes are buffered and applied together with one version at the end,
+    /// so other transactions never see intermediate values.
+    fn apply_committed(&mut self, txn_data: &TxnData) -> (TxnData, Vec<VersionedWrite>) {
+        let version = self.next_version();
+        let mut writes: Vec<VersionedWrite> = Vec::new();
+        let mut txn = Vec::with_capacity(txn_data.txn.len());
+        for func in &txn_data.txn {
+            match *func {
+                TxnFunc::Read { key, .. } => txn.push(TxnFunc::Read {
+                    key,
+                    value: writes
+                        .iter()
+                        .find(|w| w.key == key)
+                        .map(|w| w.value)
+                        .or_else(|| self.get(key)),
+                }),
+                TxnFunc::Write { key, value } => {
+                    writes.retain(|w| w.key != key);
+                    writes.push(VersionedWrite {
+                        key,
+                        value,
+                        version: version.clone(),
+                    });
+                    txn.push(func.clone());
+                }
+            }
+        }
+        self.apply(&writes);
+        (TxnData { txn }, writes)
+    }
+
+    /// Applies local or replicated writes.
+    pub fn apply(&mut self, writes: &[VersionedWrite]) {
+        for write in writes {
+            self.clock = self.clock.max(write.version.counter);
+            match self.values.get(&write.key) {
+                Some((version, _)) if *version >= write.version => {}
+                _ => {
+                    self.values
+                        .insert(write.key, (write.version.clone(), write.value));
+                }
+            }
+        }
+    }
+
+    fn get(&self, key: KeyValue) -> Option<RegisterValue> {
+        self.values.get(&key).map(|(_, value)| *value)
+    }
+
+    fn next_version(&mut self) -> Version {
+        self.clock += 1;
+        Version {
+            counter: self.clock,
+            node_id: self.node_id.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod registers_tests {
+    use serde_json::json;
+
+    use super::Registers;
+    use crate::protocol::txn_rw_register::*;
+    use crate::workloads::txn_rw_register::Isolation;
+
+    fn txn(value: serde_json::Value) -> TxnData {
+        serde_json::from_value(json!({ "txn": value })).unwrap()
+    }
+
+    fn result(data: TxnData) -> serde_json::Value {
+        serde_json::to_value(data.txn).unwrap()
+    }
+
+    #[test]
+    fn read_committed_replicates_last_writes() {
+        let mut registers = Registers::new("n1".to_owned());
+        let (res, writes) = registers.apply_txn(
+            &txn(json!([
+                ["w", 1, 1],
+                ["r", 1, null],
+                ["w", 1, 2],
+                ["w", 2, 3],
+                ["r", 3, null]
+            ])),
+            Isolation::ReadCommitted,
+        );
+        assert_eq!(
+            result(res),
+            json!([
+                ["w", 1, 1],
+                ["r", 1, 1],
+                ["w", 1, 2],
+                ["w", 2, 3],
+                ["r", 3, null]
+            ])
+        );
+        let values: Vec<_> = writes.iter().map(|w| (w.key, w.value)).collect();
+        assert_eq!(values, vec![(1, 2), (2, 3)]);
+        assert_eq!(writes[0].version, writes[1].version);
+    }
+
+    #[test]
+    fn read_uncommitted_writes_share_the_version() {
+        let mut registers = Registers::new("n1".to_owned());
+        let (res, writes) = registers.apply_txn(
+            &txn(json!([
+                ["w", 1, 1],
+                ["r", 1, null],
+                ["w", 2, 3],
+                ["w", 1, 2],
+                ["r", 1, null]
+            ])),
+            Isolation::ReadUncommitted,
+        );
+        assert_eq!(
+            result(res),
+            json!([
+                ["w", 1, 1],
+                ["r", 1, 1],
+                ["w", 2, 3],
+                ["w", 1, 2],
+                ["r", 1, 2]
+            ])
+        );
+        let values: Vec<_> = writes.iter().map(|w| (w.key, w.value)).collect();
+        assert_eq!(values, vec![(2, 3), (1, 2)]);
+        assert_eq!(writes[0].version, writes[1].version);
+    }
+
+    /// Two transactions writing the same keys win on all of them or none,
+    /// whichever order their writes arrive in.
+    #[test]
+    fn concurrent_transactions_have_no_write_cycle() {
+        for isolation in [Isolation::ReadUncommitted, Isolation::ReadCommitted] {
+            let mut n1 = Registers::new("n1".to_owned());
+            let mut n2 = Registers::new("n2".to_owned());
+            let (_, w1) = n1.apply_txn(&txn(json!([["w", 1, 1], ["w", 2, 1]])), isolation);
+            let (_, w2) = n2.apply_txn(&txn(json!([["w", 2, 2], ["w", 1, 2]])), isolation);
+            for write in w2.iter().rev() {
+                n1.apply(std::slice::from_ref(write));
+            }
+            n2.apply(&w1);
+            let read = txn(json!([["r", 1, null], ["r", 2, null]]));
+            let expected = json!([["r", 1, 2], ["r", 2, 2]]);
+            assert_eq!(result(n1.apply_txn(&read, isolation).0), expected);
+            assert_eq!(result(n2.apply_txn(&read, isolation).0), expected);
+        }
+    }
+
+    #[test]
+    fn nodes_converge_on_highest_version() {
+        let mut n1 = Registers::new("n1".to_owned());
+        let mut n2 = Registers::new("n2".to_owned());
+        let isolation = Isolation::ReadCommitted;
+        let (_, w1) = n1.apply_txn(&txn(json!([["w", 1, 1], ["w", 2, 1]])), isolation);
+        let (_, w2) = n2.apply_txn(&txn(json!([["w", 1, 2], ["w", 2, 2]])), isolation);
+        n1.apply(&w2);
+        n2.apply(&w1);
+        let read = txn(json!([["r", 1, null], ["r", 2, null]]));
+        let expected = json!([["r", 1, 2], ["r", 2, 2]]);
+        assert_eq!(result(n1.apply_txn(&read, isolation).0), expected);
+        assert_eq!(result(n2.apply_txn(&read, isolation).0), expected);
+
+        // A write after reading a replicated value gets a higher version.
+        let (_, w3) = n1.apply_txn(&txn(json!([["w", 1, 3]])), isolation);
+        assert!(w3[0].version > w2[0].version);
+        n2.apply(&w3);
+        assert_eq!(
+            result(n2.apply_txn(&read, isolation).0),
+            json!([["r", 1, 3], ["r", 2, 2]])
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, HashMap};
+
+use tokio::time::{Duration, Instant};
+
+use crate::protocol::{txn_rw_register::*, MessageId, NodeId};
+
+/// Unacknowledged messages remembered per node, the oldest are dropped first
+/// as the retransmissions carry their writes too unless they were replaced.
+const MAX_IN_FLIGHT: usize = 32;
+
+/// Writes every other node hasn't acknowledged yet. Only the newest write of
+/// a key is kept, so a node that can't be reached costs one write per key, and
+/// all of them are resent in one batch once its deadline passes.
+pub struct PendingWrites {
+    retry_interval: Duration,
+    peers: HashMap<NodeId, Peer>,
+}
+
+#[derive(Default)]
+struct Peer {
+    writes: BTreeMap<KeyValue, VersionedWrite>,
+    in_flight: BTreeMap<MessageId, Vec<VersionedWrite>>,
+    retry_at: Option<Instant>,
+}
+
+impl Peer {
+    fn send(&mut self, msg_id: MessageId, writes: Vec<VersionedWrite>) {
+        self.in_flight.insert(msg_id, writes);
+        while self.in_flight.len() > MAX_IN_FLIGHT {
+            self.in_flight.pop_first();
+        }
+    }
+}
+
+pub type Batch = (NodeId, MessageId, Vec<VersionedWrite>);
+
+impl PendingWrites {
+    pub fn new(peers: impl IntoIterator<Item = NodeId>, retry_interval: Duration) -> Self {
+        Self {
+            retry_interval,
+            peers: peers
+                .into_iter()
+                .map(|node_id| (node_id, Peer::default()))
+                .collect(),
+        }
+    }
+
+    /// Merges the writes into the pending ones and returns a batch of those
+    /// newer than what every node has pending, to send right away.
+    pub fn add(
+        &mut self,
+        writes: &[VersionedWrite],
+        now: Instant,
+        mut gen_msg_id: impl FnMut() -> MessageId,
+    ) -> Vec<Batch> {
+        let mut batches = Vec::new();
+        for (node_id, peer) in self.peers.iter_mut() {
+            let mut batch = Vec::new();
+            for write in writes {
+                match peer.writes.get(&write.key) {
+                    Some(pending) if pending.version >= write.version => {}
+                    _ => {
+                        peer.writes.insert(write.key, write.clone());
+                        batch.push(write.clone());
+                    }
+                }
+            }
+            if batch.is_empty() {
+                continue;
+            }
+            let msg_id = gen_msg_id();
+            peer.send(msg_id, batch.clone());
+            peer.retry_at.get_or_insert(now + self.retry_interval);
+            batches.push((node_id.clone(), msg_id, batch));
+        }
+        batches
+    }
+
+    /// Writes of the message are done unless newer ones replaced them since.
+    pub fn acked(&mut self, from: &str, msg_id: MessageId) {
+        let Some(peer) = self.peers.get_mut(from) else {
+            return;
+        };
+        for write in peer.in_flight.remove(&msg_id).unwrap_or_default() {
+            if peer.writes.get(&write.key) == Some(&write) {
+                peer.writes.remove(&write.key);
+            }
+        }
+        if peer.writes.is_empty() {
+            peer.in_flight.clear();
+            peer.retry_at = None;
+        }
+    }
+
+    /// One batch of all pending writes for every node past its deadline.
+    pub fn retransmissions(
+        &mut self,
+        now: Instant,
+        mut gen_msg_id: impl FnMut() -> MessageId,
+    ) -> Vec<Batch> {
+        let mut batches = Vec::new();
+        for (node_id, peer) in self.peers.iter_mut() {
+            if peer.retry_at.is_none_or(|at| at > now) {
+                continue;
+            }
+            let msg_id = gen_msg_id();
+            let writes: Vec<_> = peer.writes.values().cloned().collect();
+            peer.send(msg_id, writes.clone());
+            peer.retry_at = Some(now + self.retry_interval);
+            batches.push((node_id.clone(), msg_id, writes));
+        }
+        batches
+    }
+}
+
+#[cfg(test)]
+mod replication_tests {
+    use tokio::time::{Duration, Instant};
+
+    use super::PendingWrites;
+    use crate::protocol::txn_rw_register::{Version, VersionedWrite};
+
+    const RETRY: Duration = Duration::from_secs(1);
+
+    fn write(key: u64, value: u64, counter: u64) -> VersionedWrite {
+        VersionedWrite {
+            key,
+            value,
+            version: Version {
+                counter,
+                node_id: "n1".to_owned(),
+            },
+        }
+    }
+
+    fn values(batch: &[VersionedWrite]) -> Vec<(u64, u64)> {
+        batch.iter().map(|w| (w.key, w.value)).collect()
+    }
+
+    #[test]
+    fn merges_writes_into_pending_ones() {
+        let mut pending = PendingWrites::new(["n2".to_owned()], RETRY);
+        let now = Instant::now();
+        pending.add(&[write(1, 1, 1), write(2, 1, 1)], now, || 1);
+        let batches = pending.add(&[write(1, 2, 2), write(2, 0, 0)], now, || 2);
+        assert_eq!(values(&batches[0].2), vec![(1, 2)]);
+        assert!(pending.retransmissions(now, || 3).is_empty());
+
+        let batches = pending.retransmissions(now + RETRY, || 3);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(values(&batches[0].2), vec![(1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn keeps_writes_replaced_after_sending() {
+        let mut pending = PendingWrites::new(["n2".to_owned(), "n3".to_owned()], RETRY);
+        let now = Instant::now();
+        pending.add(&[write(1, 1, 1), write(2, 1, 1)], now, || 1);
+        pending.add(&[write(1, 2, 2)], now, || 2);
+
+        pending.acked("n2", 1);
+        let batches = pending.retransmissions(now + RETRY, || 3);
+        let to_n2 = batches
+            .iter()
+            .find(|(node_id, ..)| node_id == "n2")
+            .unwrap();
+        assert_eq!(values(&to_n2.2), vec![(1, 2)]);
+
+        // An ack of a message sent before the retransmission still counts.
+        pending.acked("n2", 2);
+        let batches = pending.retransmissions(now + 2 * RETRY, || 4);
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].0, "n3");
+    }
+}
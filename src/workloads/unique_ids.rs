@@ -0,0 +1,230 @@
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+use super::Workload;
+use crate::{
+    cli::{CliError, OptionSpec, Options},
+    io::{blocking::receive_msg, send_msg},
+    logging::{self, MsgSpan},
+    protocol::{unique_ids::*, NodeId},
+};
+
+pub static OPTIONS: &[OptionSpec] = &[OptionSpec {
+    name: "format",
+    description: "`counter` generates `<node id>-<counter>` strings, `snowflake` 64 bit numbers",
+    default: "counter",
+}];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Counter,
+    Snowflake,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "counter" => Ok(Format::Counter),
+            "snowflake" => Ok(Format::Snowflake),
+            other => Err(format!("expected counter or snowflake, got {other}")),
+        }
+    }
+}
+
+pub struct UniqueIds;
+
+impl Workload for UniqueIds {
+    fn name(&self) -> &'static str {
+        "unique-ids"
+    }
+
+    fn description(&self) -> &'static str {
+        "Generates globally unique ids without coordination between nodes"
+    }
+
+    fn maelstrom_workload(&self) -> Option<&'static str> {
+        Some("unique-ids")
+    }
+
+    fn options(&self) -> &'static [OptionSpec] {
+        OPTIONS
+    }
+
+    fn run(&self, options: &Options) -> Result<(), CliError> {
+        run(options.get("format")?);
+        Ok(())
+    }
+}
+
+pub fn run(format: Format) {
+    log::info!("Running unique-ids workload");
+    let mut generator = init(format);
+    loop {
+        let msg: Message = receive_msg();
+        MsgSpan::of(&msg).sync_scope(|| {
+            if let BodyData::Generate = msg.body.data {
+                let id = generator.next(now_millis());
+                send_msg(&msg.create_response(BodyData::GenerateOk { id }));
+            } else {
+                log::warn!("Ignoring unexpected message {:?}", msg);
+            }
+        })
+    }
+}
+
+fn init(format: Format) -> IdGenerator {
+    let msg: Message = receive_msg();
+    if let BodyData::Init(ref init) = msg.body.data {
+        logging::set_node_id(&init.node_id);
+        log::info!("Init node {}", init.node_id);
+        let generator = IdGenerator::new(format, &init.node_id, &init.node_ids);
+        send_msg(&msg.create_response(BodyData::InitOk));
+        generator
+    } else {
+        panic!("Expected init msg");
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Clock before unix epoch")
+        .as_millis() as u64
+}
+
+/// 2020-01-01T00:00:00Z, snowflake timestamps count from it.
+const SNOWFLAKE_EPOCH_MS: u64 = 1_577_836_800_000;
+const NODE_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+
+/// Ids are unique as long as node ids are, so nodes never talk to each other.
+pub enum IdGenerator {
+    Counter {
+        node_id: NodeId,
+        next: u64,
+    },
+    /// 41 bits of milliseconds, 10 bits of node index and 12 bits of sequence.
+    /// The timestamp never goes back, it runs ahead of the clock if it does or
+    /// if the sequence of a millisecond is exhausted.
+    Snowflake {
+        node_index: u64,
+        last_ms: u64,
+        sequence: u64,
+    },
+}
+
+impl IdGenerator {
+    pub fn new(format: Format, node_id: &str, node_ids: &[NodeId]) -> Self {
+        match format {
+            Format::Counter => IdGenerator::Counter {
+                node_id: node_id.to_owned(),
+                next: 0,
+            },
+            Format::Snowflake => {
+                let node_index = node_ids
+                    .iter()
+                    .position(|id| id == node_id)
+                    .expect("Node id is not in node ids") as u64;
+                assert!(
+                    node_index < 1 << NODE_BITS,
+                    "Snowflake ids support up to {} nodes",
+                    1 << NODE_BITS
+                );
+                IdGenerator::Snowflake {
+                    node_index,
+                    last_ms: 0,
+                    sequence: 0,
+                }
+            }
+        }
+    }
+
+    pub fn next(&mut self, now_ms: u64) -> Value {
+        match self {
+            IdGenerator::Counter { node_id, next } => {
+                *next += 1;
+                json!(format!("{node_id}-{next}"))
+            }
+            IdGenerator::Snowflake {
+                node_index,
+                last_ms,
+                sequence,
+            } => {
+                let now_ms = now_ms.saturating_sub(SNOWFLAKE_EPOCH_MS);
+                if now_ms > *last_ms {
+                    *last_ms = now_ms;
+                    *sequence = 0;
+                } else if *sequence == MAX_SEQUENCE {
+                    *last_ms += 1;
+                    *sequence = 0;
+                } else {
+                    *sequence += 1;
+                }
+                json!(
+                    *last_ms << (NODE_BITS + SEQUENCE_BITS)
+                        | *node_index << SEQUENCE_BITS
+                        | *sequence
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod unique_ids_tests {
+    use std::collections::HashSet;
+
+    use super::{Format, IdGenerator, SNOWFLAKE_EPOCH_MS};
+
+    fn node_ids(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("n{i}")).collect()
+    }
+
+    /// Nodes take turns generating ids with the clock standing still for a
+    /// while and then going back.
+    fn generate(format: Format) -> Vec<serde_json::Value> {
+        let node_ids = node_ids(5);
+        let mut generators: Vec<_> = node_ids
+            .iter()
+            .map(|id| IdGenerator::new(format, id, &node_ids))
+            .collect();
+        let clock = [SNOWFLAKE_EPOCH_MS + 10, SNOWFLAKE_EPOCH_MS + 5];
+        let mut ids = Vec::new();
+        for now in clock {
+            for _ in 0..5000 {
+                for generator in generators.iter_mut() {
+                    ids.push(generator.next(now));
+                }
+            }
+        }
+        ids
+    }
+
+    #[test]
+    fn unique_across_nodes() {
+        for format in [Format::Counter, Format::Snowflake] {
+            let ids = generate(format);
+            let distinct: HashSet<_> = ids.iter().map(|id| id.to_string()).collect();
+            assert_eq!(distinct.len(), ids.len(), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn snowflake_ids_increase_on_every_node() {
+        let node_ids = node_ids(2);
+        let mut generator = IdGenerator::new(Format::Snowflake, "n1", &node_ids);
+        let ids: Vec<_> = [20, 20, 10, 30]
+            .into_iter()
+            .chain(std::iter::repeat_n(30, 5000))
+            .map(|ms| generator.next(SNOWFLAKE_EPOCH_MS + ms).as_u64().unwrap())
+            .collect();
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(ids[0] >> 22, 20);
+        assert_eq!(ids[0] >> 12 & 0x3ff, 1);
+    }
+}